@@ -0,0 +1,275 @@
+//! Fluent construction of page content.
+//!
+//! There is one function per tag accepted by Telegraph, so only valid tags can be built.
+//!
+//! ```
+//! use telegraph_rs::{builder::*, nodes};
+//!
+//! let content = nodes![
+//!     h3().text("Hello"),
+//!     p().text("Welcome to ").child(a("https://telegra.ph").text("Telegraph")).text("!"),
+//!     figure().image("/file/6a5b15e7eb4d7329ca7af.jpg").caption("A picture"),
+//!     ul().item("one").item(b().text("two")),
+//!     hr(),
+//! ];
+//! assert_eq!(content.len(), 5);
+//! ```
+use crate::{Node, NodeElement};
+use std::collections::HashMap;
+
+/// Build a `Vec<Node>` from anything convertible into [`Node`],
+/// such as strings and the builders of this module.
+#[macro_export]
+macro_rules! nodes {
+    ($($node:expr),* $(,)?) => {
+        ::std::vec::Vec::<$crate::Node>::from([$($crate::Node::from($node)),*])
+    };
+}
+
+/// Builder of a [`NodeElement`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementBuilder {
+    element: NodeElement,
+}
+
+impl ElementBuilder {
+    fn new(tag: &str) -> Self {
+        ElementBuilder {
+            element: NodeElement {
+                tag: tag.to_owned(),
+                attrs: None,
+                children: None,
+            },
+        }
+    }
+
+    fn attr(mut self, name: &str, value: &str) -> Self {
+        self.element
+            .attrs
+            .get_or_insert_with(HashMap::new)
+            .insert(name.to_owned(), Some(value.to_owned()));
+        self
+    }
+
+    /// Append a text node.
+    pub fn text<S: Into<String>>(self, text: S) -> Self {
+        self.child(Node::Text(text.into()))
+    }
+
+    /// Append a child node.
+    pub fn child<N: Into<Node>>(mut self, child: N) -> Self {
+        self.element
+            .children
+            .get_or_insert_with(Vec::new)
+            .push(child.into());
+        self
+    }
+
+    /// Append every node of `children`.
+    pub fn children<I, N>(self, children: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<Node>,
+    {
+        children.into_iter().fold(self, Self::child)
+    }
+
+    pub fn build(self) -> Node {
+        Node::NodeElement(self.element)
+    }
+}
+
+impl From<ElementBuilder> for Node {
+    fn from(builder: ElementBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builder of an `ul` or `ol` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListBuilder {
+    list: ElementBuilder,
+}
+
+impl ListBuilder {
+    /// Append a `li` containing `item`, or `item` itself if it is already a `li`.
+    pub fn item<N: Into<Node>>(mut self, item: N) -> Self {
+        let item = match item.into() {
+            Node::NodeElement(li) if li.tag == "li" => Node::NodeElement(li),
+            item => li().child(item).build(),
+        };
+        self.list = self.list.child(item);
+        self
+    }
+
+    /// Append a `li` for every node of `items`.
+    pub fn items<I, N>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<Node>,
+    {
+        items.into_iter().fold(self, Self::item)
+    }
+
+    pub fn build(self) -> Node {
+        self.list.build()
+    }
+}
+
+impl From<ListBuilder> for Node {
+    fn from(builder: ListBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builder of a `figure`, with its media followed by an optional caption.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FigureBuilder {
+    media: Vec<Node>,
+    caption: Option<ElementBuilder>,
+}
+
+impl FigureBuilder {
+    /// Add an `img`.
+    pub fn image(mut self, src: &str) -> Self {
+        self.media.push(img(src).build());
+        self
+    }
+
+    /// Add a `video`.
+    pub fn video(mut self, src: &str) -> Self {
+        self.media.push(video(src).build());
+        self
+    }
+
+    /// Add an `iframe` embed.
+    pub fn iframe(mut self, src: &str) -> Self {
+        self.media.push(iframe(src).build());
+        self
+    }
+
+    /// Append `caption` to the `figcaption`.
+    pub fn caption<N: Into<Node>>(mut self, caption: N) -> Self {
+        self.caption = Some(self.caption.unwrap_or_else(figcaption).child(caption));
+        self
+    }
+
+    pub fn build(self) -> Node {
+        let figure = ElementBuilder::new("figure").children(self.media);
+        match self.caption {
+            Some(caption) => figure.child(caption),
+            None => figure,
+        }
+        .build()
+    }
+}
+
+impl From<FigureBuilder> for Node {
+    fn from(builder: FigureBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Link to `href`.
+pub fn a(href: &str) -> ElementBuilder {
+    ElementBuilder::new("a").attr("href", href)
+}
+
+pub fn aside() -> ElementBuilder {
+    ElementBuilder::new("aside")
+}
+
+pub fn b() -> ElementBuilder {
+    ElementBuilder::new("b")
+}
+
+pub fn blockquote() -> ElementBuilder {
+    ElementBuilder::new("blockquote")
+}
+
+pub fn br() -> ElementBuilder {
+    ElementBuilder::new("br")
+}
+
+pub fn code() -> ElementBuilder {
+    ElementBuilder::new("code")
+}
+
+pub fn em() -> ElementBuilder {
+    ElementBuilder::new("em")
+}
+
+pub fn figcaption() -> ElementBuilder {
+    ElementBuilder::new("figcaption")
+}
+
+pub fn figure() -> FigureBuilder {
+    FigureBuilder::default()
+}
+
+pub fn h3() -> ElementBuilder {
+    ElementBuilder::new("h3")
+}
+
+pub fn h4() -> ElementBuilder {
+    ElementBuilder::new("h4")
+}
+
+pub fn hr() -> ElementBuilder {
+    ElementBuilder::new("hr")
+}
+
+pub fn i() -> ElementBuilder {
+    ElementBuilder::new("i")
+}
+
+/// Embed of `src`, e.g. `/embed/youtube?url=...`.
+pub fn iframe(src: &str) -> ElementBuilder {
+    ElementBuilder::new("iframe").attr("src", src)
+}
+
+/// Image from `src`.
+pub fn img(src: &str) -> ElementBuilder {
+    ElementBuilder::new("img").attr("src", src)
+}
+
+pub fn li() -> ElementBuilder {
+    ElementBuilder::new("li")
+}
+
+pub fn ol() -> ListBuilder {
+    ListBuilder {
+        list: ElementBuilder::new("ol"),
+    }
+}
+
+pub fn p() -> ElementBuilder {
+    ElementBuilder::new("p")
+}
+
+pub fn pre() -> ElementBuilder {
+    ElementBuilder::new("pre")
+}
+
+pub fn s() -> ElementBuilder {
+    ElementBuilder::new("s")
+}
+
+pub fn strong() -> ElementBuilder {
+    ElementBuilder::new("strong")
+}
+
+pub fn u() -> ElementBuilder {
+    ElementBuilder::new("u")
+}
+
+pub fn ul() -> ListBuilder {
+    ListBuilder {
+        list: ElementBuilder::new("ul"),
+    }
+}
+
+/// Video from `src`.
+pub fn video(src: &str) -> ElementBuilder {
+    ElementBuilder::new("video").attr("src", src)
+}
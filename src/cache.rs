@@ -0,0 +1,394 @@
+//! Remembering uploaded files to avoid uploading them again.
+//!
+//! Files are identified by the SHA-256 hash of their content, mapped to the `src`
+//! returned when they were uploaded. Enable a cache with
+//! [`AccountBuilder::upload_cache`](crate::AccountBuilder::upload_cache).
+//!
+//! ```
+//! # async fn run() -> Result<(), telegraph_rs::Error> {
+//! use std::sync::Arc;
+//! use telegraph_rs::{cache::{CacheKey, JsonFileCache, UploadCache}, AccountBuilder};
+//!
+//! let cache = Arc::new(JsonFileCache::open("uploads.json")?);
+//! let telegraph = AccountBuilder::new("bot")
+//!     .upload_cache(cache.clone())
+//!     .create()
+//!     .await?;
+//! // Uploaded once, then taken from the cache.
+//! let logo = telegraph.upload_media(&["logo.png"]).await?;
+//! let logo = telegraph.upload_media(&["logo.png"]).await?;
+//! // Upload it again next time.
+//! cache.remove(&CacheKey::of(&std::fs::read("logo.png")?))?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{Error, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// SHA-256 hash of the content of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    /// Hash `bytes`.
+    pub fn of(bytes: &[u8]) -> Self {
+        CacheKey(Sha256::digest(bytes).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Lowercase hexadecimal.
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl FromStr for CacheKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidCacheKey(s.to_owned());
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(invalid());
+        }
+        let mut key = [0; 32];
+        for (byte, hex) in key.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        }
+        Ok(CacheKey(key))
+    }
+}
+
+/// Storage of the `src` of uploaded files.
+///
+/// Implemented by [`MemoryCache`], [`JsonFileCache`] and, with the `sqlite-cache`
+/// feature, [`SqliteCache`]. Wrap a cache in an [`Arc`] to keep a handle on it, for
+/// instance to invalidate entries.
+///
+/// Uploads call it on a blocking thread, so implementations may read and write files.
+pub trait UploadCache: fmt::Debug + Send + Sync {
+    /// `src` of the file with content hash `key`, if uploaded before.
+    fn get(&self, key: &CacheKey) -> Result<Option<String>>;
+
+    /// Remember that the file with content hash `key` was uploaded to `src`.
+    fn insert(&self, key: &CacheKey, src: &str) -> Result<()>;
+
+    /// Remember several uploaded files at once.
+    ///
+    /// Calls [`insert`](Self::insert) for each of them by default, caches stored on
+    /// disk write them together instead.
+    fn insert_all(&self, entries: &[(CacheKey, String)]) -> Result<()> {
+        entries
+            .iter()
+            .try_for_each(|(key, src)| self.insert(key, src))
+    }
+
+    /// Forget the file with content hash `key`, returning whether it was known.
+    fn remove(&self, key: &CacheKey) -> Result<bool>;
+
+    /// Forget every file uploaded to `src`, returning how many were known.
+    fn remove_src(&self, src: &str) -> Result<usize>;
+
+    /// Forget every file.
+    fn clear(&self) -> Result<()>;
+}
+
+impl<C: UploadCache + ?Sized> UploadCache for Arc<C> {
+    fn get(&self, key: &CacheKey) -> Result<Option<String>> {
+        (**self).get(key)
+    }
+
+    fn insert(&self, key: &CacheKey, src: &str) -> Result<()> {
+        (**self).insert(key, src)
+    }
+
+    fn insert_all(&self, entries: &[(CacheKey, String)]) -> Result<()> {
+        (**self).insert_all(entries)
+    }
+
+    fn remove(&self, key: &CacheKey) -> Result<bool> {
+        (**self).remove(key)
+    }
+
+    fn remove_src(&self, src: &str) -> Result<usize> {
+        (**self).remove_src(src)
+    }
+
+    fn clear(&self) -> Result<()> {
+        (**self).clear()
+    }
+}
+
+/// A cache lost when dropped.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<CacheKey, String>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of files in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl UploadCache for MemoryCache {
+    fn get(&self, key: &CacheKey) -> Result<Option<String>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn insert(&self, key: &CacheKey, src: &str) -> Result<()> {
+        self.entries.lock().unwrap().insert(*key, src.to_owned());
+        Ok(())
+    }
+
+    fn remove(&self, key: &CacheKey) -> Result<bool> {
+        Ok(self.entries.lock().unwrap().remove(key).is_some())
+    }
+
+    fn remove_src(&self, src: &str) -> Result<usize> {
+        Ok(remove_src(&mut self.entries.lock().unwrap(), src))
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.entries.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// Remove the entries of `src` from `entries`, returning how many there were.
+fn remove_src<K>(entries: &mut HashMap<K, String>, src: &str) -> usize {
+    let len = entries.len();
+    entries.retain(|_, cached| cached != src);
+    len - entries.len()
+}
+
+/// A cache kept in a JSON file, mapping hexadecimal content hashes to `src`.
+///
+/// The whole file is read when opened and rewritten on every change, once for all the
+/// files of an upload, which suits caches of up to a few thousand files.
+#[derive(Debug)]
+pub struct JsonFileCache {
+    path: PathBuf,
+    entries: Mutex<Entries>,
+    /// Version of the entries last written to the file.
+    saved: Mutex<u64>,
+}
+
+/// Entries of a [`JsonFileCache`], with the number of changes made to them.
+#[derive(Debug)]
+struct Entries {
+    map: HashMap<CacheKey, String>,
+    version: u64,
+}
+
+impl JsonFileCache {
+    /// Open the cache stored at `path`, which is created on the first upload if missing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let map = match std::fs::read(&path) {
+            Ok(json) => serde_json::from_slice::<HashMap<String, String>>(&json)?
+                .into_iter()
+                .map(|(key, src)| Ok((key.parse()?, src)))
+                .collect::<Result<_>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(JsonFileCache {
+            path,
+            entries: Mutex::new(Entries { map, version: 0 }),
+            saved: Mutex::new(0),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Change the entries with `f`, which returns whether it changed anything, and
+    /// write them to the file if so.
+    fn update<R>(&self, f: impl FnOnce(&mut HashMap<CacheKey, String>) -> (R, bool)) -> Result<R> {
+        let (result, json, version) = {
+            let mut entries = self.entries.lock().unwrap();
+            let (result, changed) = f(&mut entries.map);
+            if !changed {
+                return Ok(result);
+            }
+            entries.version += 1;
+            let sorted: std::collections::BTreeMap<_, _> = entries
+                .map
+                .iter()
+                .map(|(key, src)| (key.to_string(), src))
+                .collect();
+            (result, serde_json::to_vec_pretty(&sorted)?, entries.version)
+        };
+        // Written without blocking readers, unless a newer version already was.
+        let mut saved = self.saved.lock().unwrap();
+        if *saved < version {
+            let mut tmp = self.path.clone().into_os_string();
+            tmp.push(".tmp");
+            std::fs::write(&tmp, json)?;
+            std::fs::rename(&tmp, &self.path)?;
+            *saved = version;
+        }
+        Ok(result)
+    }
+}
+
+impl UploadCache for JsonFileCache {
+    fn get(&self, key: &CacheKey) -> Result<Option<String>> {
+        Ok(self.entries.lock().unwrap().map.get(key).cloned())
+    }
+
+    fn insert(&self, key: &CacheKey, src: &str) -> Result<()> {
+        self.insert_all(&[(*key, src.to_owned())])
+    }
+
+    fn insert_all(&self, entries: &[(CacheKey, String)]) -> Result<()> {
+        self.update(|map| {
+            let mut changed = false;
+            for (key, src) in entries {
+                if map.get(key) != Some(src) {
+                    map.insert(*key, src.clone());
+                    changed = true;
+                }
+            }
+            ((), changed)
+        })
+    }
+
+    fn remove(&self, key: &CacheKey) -> Result<bool> {
+        self.update(|map| {
+            let removed = map.remove(key).is_some();
+            (removed, removed)
+        })
+    }
+
+    fn remove_src(&self, src: &str) -> Result<usize> {
+        self.update(|map| {
+            let removed = remove_src(map, src);
+            (removed, removed > 0)
+        })
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.update(|map| {
+            map.clear();
+            ((), true)
+        })
+    }
+}
+
+/// A cache kept in the `upload_cache` table of a SQLite database.
+#[cfg(feature = "sqlite-cache")]
+#[derive(Debug)]
+pub struct SqliteCache {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite-cache")]
+impl SqliteCache {
+    /// Open the database at `path`, creating it and the table if missing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_connection(rusqlite::Connection::open(path)?)
+    }
+
+    /// Use the database opened by `connection`, creating the table if missing.
+    pub fn with_connection(connection: rusqlite::Connection) -> Result<Self> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS upload_cache (
+                hash BLOB PRIMARY KEY,
+                src TEXT NOT NULL
+            )",
+            [],
+        )?;
+        Ok(SqliteCache {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite-cache")]
+impl UploadCache for SqliteCache {
+    fn get(&self, key: &CacheKey) -> Result<Option<String>> {
+        use rusqlite::OptionalExtension;
+        let src = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT src FROM upload_cache WHERE hash = ?1",
+                [&key.0[..]],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(src)
+    }
+
+    fn insert(&self, key: &CacheKey, src: &str) -> Result<()> {
+        self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO upload_cache (hash, src) VALUES (?1, ?2)",
+            rusqlite::params![&key.0[..], src],
+        )?;
+        Ok(())
+    }
+
+    fn insert_all(&self, entries: &[(CacheKey, String)]) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for (key, src) in entries {
+            transaction.execute(
+                "INSERT OR REPLACE INTO upload_cache (hash, src) VALUES (?1, ?2)",
+                rusqlite::params![&key.0[..], src],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &CacheKey) -> Result<bool> {
+        let removed = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM upload_cache WHERE hash = ?1", [&key.0[..]])?;
+        Ok(removed > 0)
+    }
+
+    fn remove_src(&self, src: &str) -> Result<usize> {
+        let removed = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM upload_cache WHERE src = ?1", [src])?;
+        Ok(removed)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM upload_cache", [])?;
+        Ok(())
+    }
+}
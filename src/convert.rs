@@ -0,0 +1,420 @@
+//! HTML conversion reporting everything that could not be kept.
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+use crate::Result;
+use crate::{Node, NodeElement, SanitizePolicy};
+use std::fmt;
+
+/// Tags laid out as blocks, whitespace around them is not significant.
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// Something left out of a [`Conversion`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dropped {
+    /// An element replaced by its children, e.g. `span`.
+    Tag { tag: String },
+    /// An element removed with its content, e.g. `script`.
+    Element { tag: String },
+    /// An attribute removed from an element, e.g. `class`.
+    Attribute {
+        tag: String,
+        attribute: String,
+        value: Option<String>,
+    },
+    /// A node that is neither an element nor text, e.g. a comment.
+    Node { kind: &'static str, content: String },
+}
+
+impl fmt::Display for Dropped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dropped::Tag { tag } => write!(f, "unwrapped <{}>", tag),
+            Dropped::Element { tag } => write!(f, "removed <{}> with its content", tag),
+            Dropped::Attribute {
+                tag,
+                attribute,
+                value: Some(value),
+            } => write!(f, "removed {}={:?} from <{}>", attribute, value, tag),
+            Dropped::Attribute {
+                tag,
+                attribute,
+                value: None,
+            } => write!(f, "removed {} from <{}>", attribute, tag),
+            Dropped::Node { kind, content } => write!(f, "removed {} {:?}", kind, content),
+        }
+    }
+}
+
+/// Nodes converted from markup, with what was dropped to make them valid.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conversion {
+    pub nodes: Vec<Node>,
+    pub dropped: Vec<Dropped>,
+}
+
+impl Conversion {
+    /// Whether nothing was dropped.
+    pub fn is_lossless(&self) -> bool {
+        self.dropped.is_empty()
+    }
+}
+
+/// Options of [`convert_html`].
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    policy: SanitizePolicy,
+    newlines_to_br: bool,
+}
+
+impl ConvertOptions {
+    /// Sanitize with the default [`SanitizePolicy`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sanitize according to `policy`.
+    pub fn policy(mut self, policy: SanitizePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Turn line breaks of the text outside of `pre` into `br`, disabled by default.
+    ///
+    /// Useful for HTML written as plain text, where line breaks are meant to be kept.
+    pub fn newlines_to_br(mut self, newlines_to_br: bool) -> Self {
+        self.newlines_to_br = newlines_to_br;
+        self
+    }
+}
+
+/// Convert `html` into nodes accepted by Telegraph, reporting every dropped tag,
+/// attribute and node.
+///
+/// Fails if `html` cannot be parsed, which only happens with the `html` parser.
+///
+/// ```
+/// use telegraph_rs::{builder::*, convert::{convert_html, ConvertOptions, Dropped}, nodes};
+///
+/// let conversion = convert_html(
+///     r#"<p class="intro">Hello<!--greeting--></p>"#,
+///     &ConvertOptions::new(),
+/// )?;
+/// assert_eq!(conversion.nodes, nodes![p().text("Hello")]);
+/// assert_eq!(
+///     conversion.dropped,
+///     [
+///         Dropped::Node { kind: "comment", content: "greeting".to_owned() },
+///         Dropped::Attribute {
+///             tag: "p".to_owned(),
+///             attribute: "class".to_owned(),
+///             value: Some("intro".to_owned())
+///         },
+///     ]
+/// );
+/// # Ok::<_, telegraph_rs::Error>(())
+/// ```
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+pub fn convert_html(html: &str, options: &ConvertOptions) -> Result<Conversion> {
+    let mut dropped = vec![];
+    let nodes = normalize_whitespace(&parse(html, &mut dropped)?, options.newlines_to_br);
+    let mut conversion = options.policy.sanitize_report(&nodes);
+    dropped.append(&mut conversion.dropped);
+    conversion.dropped = dropped;
+    Ok(conversion)
+}
+
+/// Collapse whitespace of `nodes` as browsers render it.
+///
+/// Runs of whitespace become a single space, whitespace at the start and end of
+/// blocks and between blocks is removed, and the content of `pre` is kept as is.
+/// If `newlines_to_br` is true, line breaks outside of `pre` become `br` instead.
+///
+/// ```
+/// use telegraph_rs::{builder::*, convert::normalize_whitespace, nodes};
+///
+/// let nodes = nodes!["\n  ", p().text("\n  Hello,\n  ").child(b().text(" world ")), "\n"];
+/// assert_eq!(
+///     normalize_whitespace(&nodes, false),
+///     nodes![p().text("Hello, ").child(b().text("world"))]
+/// );
+/// ```
+pub fn normalize_whitespace(nodes: &[Node], newlines_to_br: bool) -> Vec<Node> {
+    let mut out = vec![];
+    whitespace_block(nodes, newlines_to_br, &mut out);
+    out
+}
+
+fn is_block(node: &Node) -> bool {
+    matches!(node, Node::NodeElement(element) if BLOCK_TAGS.contains(&element.tag.as_str()))
+}
+
+fn with_children(element: &NodeElement, children: Vec<Node>) -> Node {
+    Node::NodeElement(NodeElement {
+        tag: element.tag.clone(),
+        attrs: element.attrs.clone(),
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    })
+}
+
+/// Normalize the content of a block, made of blocks and runs of inline nodes.
+fn whitespace_block(nodes: &[Node], newlines_to_br: bool, out: &mut Vec<Node>) {
+    let mut start = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        let element = match node {
+            Node::NodeElement(element) if is_block(node) => element,
+            _ => continue,
+        };
+        whitespace_line(&nodes[start..i], newlines_to_br, out);
+        start = i + 1;
+        if element.tag == "pre" {
+            out.push(node.clone());
+        } else {
+            let mut children = vec![];
+            let element_children = element.children.as_deref().unwrap_or_default();
+            whitespace_block(element_children, newlines_to_br, &mut children);
+            out.push(with_children(element, children));
+        }
+    }
+    whitespace_line(&nodes[start..], newlines_to_br, out);
+}
+
+/// Normalize a run of inline nodes, dropping leading and trailing whitespace.
+fn whitespace_line(nodes: &[Node], newlines_to_br: bool, out: &mut Vec<Node>) {
+    let mut line = vec![];
+    // Whether the previous character is a space, true at the start of the line
+    // so that leading whitespace is dropped.
+    let mut space = true;
+    whitespace_inline(nodes, newlines_to_br, &mut space, &mut line);
+    trim_end(&mut line);
+    out.append(&mut line);
+}
+
+fn whitespace_inline(nodes: &[Node], newlines_to_br: bool, space: &mut bool, out: &mut Vec<Node>) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => {
+                let mut collapsed = String::new();
+                for c in text.chars() {
+                    if c == '\n' && newlines_to_br {
+                        push_text(out, collapsed);
+                        collapsed = String::new();
+                        push_br(out);
+                        *space = true;
+                    } else if c.is_ascii_whitespace() {
+                        if !*space {
+                            collapsed.push(' ');
+                            *space = true;
+                        }
+                    } else {
+                        collapsed.push(c);
+                        *space = false;
+                    }
+                }
+                push_text(out, collapsed);
+            }
+            Node::NodeElement(element) if element.tag == "br" => {
+                push_br(out);
+                *space = true;
+            }
+            Node::NodeElement(element) => {
+                let mut children = vec![];
+                let element_children = element.children.as_deref().unwrap_or_default();
+                whitespace_inline(element_children, newlines_to_br, space, &mut children);
+                out.push(with_children(element, children));
+                // Media are rendered like a character.
+                if matches!(element.tag.as_str(), "iframe" | "img" | "video") {
+                    *space = false;
+                }
+            }
+        }
+    }
+}
+
+fn push_text(out: &mut Vec<Node>, text: String) {
+    if !text.is_empty() {
+        out.push(Node::Text(text));
+    }
+}
+
+/// Append a `br`, dropping the whitespace before it.
+fn push_br(out: &mut Vec<Node>) {
+    trim_end(out);
+    out.push(Node::NodeElement(NodeElement {
+        tag: "br".to_owned(),
+        attrs: None,
+        children: None,
+    }));
+}
+
+/// Remove the trailing whitespace of inline nodes.
+fn trim_end(nodes: &mut Vec<Node>) {
+    match nodes.last_mut() {
+        Some(Node::Text(text)) => {
+            let len = text
+                .trim_end_matches(|c: char| c.is_ascii_whitespace())
+                .len();
+            text.truncate(len);
+            if text.is_empty() {
+                nodes.pop();
+                trim_end(nodes);
+            }
+        }
+        Some(Node::NodeElement(element)) if element.tag != "br" => {
+            if let Some(children) = &mut element.children {
+                trim_end(children);
+                if children.is_empty() {
+                    element.children = None;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+fn element(tag: String, attrs: Vec<(String, Option<String>)>, children: Vec<Node>) -> Node {
+    Node::NodeElement(NodeElement {
+        tag,
+        attrs: if attrs.is_empty() {
+            None
+        } else {
+            Some(attrs.into_iter().collect())
+        },
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    })
+}
+
+/// Parse `html` keeping every element and attribute, and reporting other nodes.
+#[cfg(feature = "kuchiki")]
+fn parse(html: &str, dropped: &mut Vec<Dropped>) -> Result<Vec<Node>> {
+    use kuchikiki::{traits::TendrilSink, NodeData, NodeRef};
+
+    fn convert(node: &NodeRef, nodes: &mut Vec<Node>, dropped: &mut Vec<Dropped>) {
+        match node.data() {
+            NodeData::Text(text) => nodes.push(Node::Text(text.borrow().clone())),
+            // Structure added by the parser.
+            NodeData::Element(data) if matches!(&*data.name.local, "html" | "head" | "body") => {
+                node.children()
+                    .for_each(|child| convert(&child, nodes, dropped));
+            }
+            NodeData::Element(data) => {
+                let attrs = data
+                    .attributes
+                    .borrow()
+                    .map
+                    .iter()
+                    .map(|(name, attr)| {
+                        let value = Some(attr.value.clone()).filter(|value| !value.is_empty());
+                        (name.local.to_string(), value)
+                    })
+                    .collect();
+                let mut children = vec![];
+                node.children()
+                    .for_each(|child| convert(&child, &mut children, dropped));
+                nodes.push(element(data.name.local.to_string(), attrs, children));
+            }
+            NodeData::Comment(comment) => dropped.push(Dropped::Node {
+                kind: "comment",
+                content: comment.borrow().clone(),
+            }),
+            NodeData::Doctype(doctype) => dropped.push(Dropped::Node {
+                kind: "doctype",
+                content: doctype.name.clone(),
+            }),
+            NodeData::ProcessingInstruction(instruction) => dropped.push(Dropped::Node {
+                kind: "processing instruction",
+                content: instruction.borrow().0.clone(),
+            }),
+            NodeData::Document(_) | NodeData::DocumentFragment => node
+                .children()
+                .for_each(|child| convert(&child, nodes, dropped)),
+        }
+    }
+
+    let document = kuchikiki::parse_html().one(html);
+    let mut nodes = vec![];
+    convert(&document, &mut nodes, dropped);
+    Ok(nodes)
+}
+
+/// Parse `html` keeping every element and attribute, and reporting other nodes.
+#[cfg(all(feature = "html", not(feature = "kuchiki")))]
+fn parse(html: &str, dropped: &mut Vec<Dropped>) -> Result<Vec<Node>> {
+    fn convert(node: &html_parser::Node, nodes: &mut Vec<Node>, dropped: &mut Vec<Dropped>) {
+        match node {
+            html_parser::Node::Text(text) => nodes.push(Node::Text(text.clone())),
+            html_parser::Node::Element(data) => {
+                let mut children = vec![];
+                data.children
+                    .iter()
+                    .for_each(|child| convert(child, &mut children, dropped));
+                if matches!(data.name.as_str(), "html" | "head" | "body") {
+                    nodes.append(&mut children);
+                    return;
+                }
+                let mut attrs: Vec<_> = data.attributes.clone().into_iter().collect();
+                if let Some(id) = &data.id {
+                    attrs.push(("id".to_owned(), Some(id.clone())));
+                }
+                if !data.classes.is_empty() {
+                    attrs.push(("class".to_owned(), Some(data.classes.join(" "))));
+                }
+                nodes.push(element(data.name.clone(), attrs, children));
+            }
+            html_parser::Node::Comment(comment) => dropped.push(Dropped::Node {
+                kind: "comment",
+                content: comment.clone(),
+            }),
+        }
+    }
+
+    let dom =
+        html_parser::Dom::parse(html).map_err(|e| crate::Error::InvalidHtml(e.to_string()))?;
+    let mut nodes = vec![];
+    dom.children
+        .iter()
+        .for_each(|child| convert(child, &mut nodes, dropped));
+    Ok(nodes)
+}
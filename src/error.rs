@@ -1,28 +1,236 @@
+use crate::{AccountField, Violation};
 use serde::Deserialize;
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub(crate) enum ApiResult<T> {
     Ok { result: T },
-    Err { ok: bool, error: String },
+    Err { error: String },
 }
 
-impl<T> Into<Result<T, Error>> for ApiResult<T> {
-    fn into(self) -> Result<T, Error> {
+impl<T> ApiResult<T> {
+    /// Convert into a `Result`, attaching the called method and page path to errors.
+    pub(crate) fn into_result(self, method: &'static str, path: Option<&str>) -> Result<T, Error> {
         match self {
             ApiResult::Ok { result: v } => Ok(v),
-            ApiResult::Err { error: e, .. } => Err(Error::ApiError(e)),
+            ApiResult::Err { error: e } => Err(Error::api(e, method, path)),
         }
     }
 }
 
+/// Kind of an error returned by the Telegraph API.
+///
+/// Parsed from the error text, unrecognized errors are kept in `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ApiErrorKind {
+    /// `ACCESS_TOKEN_INVALID`
+    AccessTokenInvalid,
+    /// `SHORT_NAME_REQUIRED`
+    ShortNameRequired,
+    /// `TITLE_REQUIRED`
+    TitleRequired,
+    /// `TITLE_TOO_LONG`
+    TitleTooLong,
+    /// `AUTHOR_NAME_TOO_LONG`
+    AuthorNameTooLong,
+    /// `AUTHOR_URL_INVALID`
+    AuthorUrlInvalid,
+    /// `CONTENT_REQUIRED`
+    ContentRequired,
+    /// `CONTENT_TEXT_REQUIRED`
+    ContentTextRequired,
+    /// `CONTENT_FORMAT_INVALID`
+    ContentFormatInvalid,
+    /// `CONTENT_TOO_BIG`
+    ContentTooBig,
+    /// `FIELDS_FORMAT_INVALID`
+    FieldsFormatInvalid,
+    /// `PAGE_NOT_FOUND`
+    PageNotFound,
+    /// `PAGE_ACCESS_DENIED`
+    PageAccessDenied,
+    /// `PAGE_SAVE_FAILED`
+    PageSaveFailed,
+    /// `FLOOD_WAIT_N`, where `N` is the number of seconds to wait before retrying.
+    FloodWait(u64),
+    /// Any other error.
+    Unknown(String),
+}
+
+impl ApiErrorKind {
+    /// Parse the error text returned by the API.
+    pub fn parse(error: &str) -> Self {
+        use ApiErrorKind::*;
+        match error {
+            "ACCESS_TOKEN_INVALID" => AccessTokenInvalid,
+            "SHORT_NAME_REQUIRED" => ShortNameRequired,
+            "TITLE_REQUIRED" => TitleRequired,
+            "TITLE_TOO_LONG" => TitleTooLong,
+            "AUTHOR_NAME_TOO_LONG" => AuthorNameTooLong,
+            "AUTHOR_URL_INVALID" => AuthorUrlInvalid,
+            "CONTENT_REQUIRED" => ContentRequired,
+            "CONTENT_TEXT_REQUIRED" => ContentTextRequired,
+            "CONTENT_FORMAT_INVALID" => ContentFormatInvalid,
+            "CONTENT_TOO_BIG" => ContentTooBig,
+            "FIELDS_FORMAT_INVALID" => FieldsFormatInvalid,
+            "PAGE_NOT_FOUND" => PageNotFound,
+            "PAGE_ACCESS_DENIED" => PageAccessDenied,
+            "PAGE_SAVE_FAILED" => PageSaveFailed,
+            _ => match error
+                .strip_prefix("FLOOD_WAIT_")
+                .and_then(|seconds| seconds.parse().ok())
+            {
+                Some(seconds) => FloodWait(seconds),
+                None => Unknown(error.to_owned()),
+            },
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ApiErrorKind::FloodWait(_) | ApiErrorKind::PageSaveFailed
+        )
+    }
+}
+
+impl fmt::Display for ApiErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ApiErrorKind::*;
+        let s = match self {
+            AccessTokenInvalid => "ACCESS_TOKEN_INVALID",
+            ShortNameRequired => "SHORT_NAME_REQUIRED",
+            TitleRequired => "TITLE_REQUIRED",
+            TitleTooLong => "TITLE_TOO_LONG",
+            AuthorNameTooLong => "AUTHOR_NAME_TOO_LONG",
+            AuthorUrlInvalid => "AUTHOR_URL_INVALID",
+            ContentRequired => "CONTENT_REQUIRED",
+            ContentTextRequired => "CONTENT_TEXT_REQUIRED",
+            ContentFormatInvalid => "CONTENT_FORMAT_INVALID",
+            ContentTooBig => "CONTENT_TOO_BIG",
+            FieldsFormatInvalid => "FIELDS_FORMAT_INVALID",
+            PageNotFound => "PAGE_NOT_FOUND",
+            PageAccessDenied => "PAGE_ACCESS_DENIED",
+            PageSaveFailed => "PAGE_SAVE_FAILED",
+            FloodWait(seconds) => return write!(f, "FLOOD_WAIT_{}", seconds),
+            Unknown(error) => error,
+        };
+        f.write_str(s)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("reqwest error: {0}")]
     ReqwestError(#[from] reqwest::Error),
-    #[error("api error: {0}")]
-    ApiError(String),
+    #[error("api error: {kind} ({method}{})", .path.as_deref().map(|p| format!(" {}", p)).unwrap_or_default())]
+    ApiError {
+        /// Parsed error returned by the API.
+        kind: ApiErrorKind,
+        /// API method that failed, e.g. `createPage`.
+        method: &'static str,
+        /// Path of the page the request was about, if any.
+        path: Option<String>,
+    },
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("account field {0} was not requested")]
+    FieldNotRequested(AccountField),
+    #[error("account field {0} is missing from the response")]
+    MissingField(AccountField),
+    #[error("invalid views query: {0}")]
+    InvalidViewsQuery(String),
+    #[error("invalid content: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidContent(Vec<Violation>),
+    #[error("invalid html: {0}")]
+    InvalidHtml(String),
+    #[cfg(feature = "markdown")]
+    #[error("raw html in markdown needs the kuchiki or html feature: {0}")]
+    UnsupportedHtml(String),
+    #[cfg(feature = "upload")]
+    #[error("invalid media source: {0}")]
+    InvalidMedia(String),
+    #[cfg(feature = "upload")]
+    #[error("local media not allowed: {}", .0.display())]
+    LocalMediaNotAllowed(std::path::PathBuf),
+    #[cfg(feature = "upload")]
+    #[error("upload reader was already consumed")]
+    ReaderConsumed,
+    #[cfg(feature = "upload")]
+    #[error("unsupported file type, only jpeg, png, gif and mp4 can be uploaded")]
+    UnsupportedFileType,
+    #[cfg(feature = "upload")]
+    #[error("file too big: {size} bytes, at most {max_size} allowed")]
+    FileTooBig { size: u64, max_size: u64 },
+    #[cfg(feature = "upload")]
+    #[error("malformed {0} file")]
+    MalformedFile(&'static str),
+    #[cfg(feature = "upload")]
+    #[error("metadata can only be stripped from files on disk or in memory")]
+    CannotStripMetadata,
+    #[cfg(feature = "image-processing")]
+    #[error("image error: {0}")]
+    ImageError(#[from] image::ImageError),
+    #[cfg(feature = "upload-cache")]
+    #[error("invalid upload cache key: {0}")]
+    InvalidCacheKey(String),
+    #[cfg(feature = "upload-cache")]
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[cfg(feature = "sqlite-cache")]
+    #[error("sqlite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+}
+
+impl Error {
+    pub(crate) fn api(error: String, method: &'static str, path: Option<&str>) -> Self {
+        Error::ApiError {
+            kind: ApiErrorKind::parse(&error),
+            method,
+            path: path.map(str::to_owned),
+        }
+    }
+
+    /// The API error kind, if this error was returned by the API.
+    pub fn api_kind(&self) -> Option<&ApiErrorKind> {
+        match self {
+            Error::ApiError { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// Whether the failed request may succeed if sent again later.
+    ///
+    /// True for flood control, timeouts, connection failures and 5xx/429 responses.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ReqwestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|status| {
+                        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            }
+            Error::ApiError { kind, .. } => kind.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Whether the server may have applied the failed request anyway.
+    ///
+    /// True when the request reached the server but its response was lost or an error,
+    /// such as after a timeout or a 5xx response.
+    pub fn may_have_been_applied(&self) -> bool {
+        match self {
+            Error::ReqwestError(e) => {
+                !e.is_connect()
+                    && !e.is_builder()
+                    && e.status() != Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+            }
+            _ => false,
+        }
+    }
 }
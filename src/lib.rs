@@ -5,45 +5,197 @@
 //! # Examples
 //!
 //! ```
+//! # #[cfg(any(feature = "kuchiki", feature = "html"))]
 //! # async fn run() -> Result<(), telegraph_rs::Error> {
-//! use telegraph_rs::{Telegraph, html_to_node};
+//! use telegraph_rs::{Telegraph, html_to_nodes};
 //!
 //! let telegraph = Telegraph::new("test_account").create().await?;
 //!
-//! let page = telegraph.create_page("title", &html_to_node("<p>Hello, world</p>"), false).await?;
+//! let page = telegraph.create_page("title", &html_to_nodes("<p>Hello, world</p>"), false).await?;
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! Content can also be built with the [`builder`] module:
+//!
+//! ```
+//! # async fn run(telegraph: telegraph_rs::Telegraph) -> Result<(), telegraph_rs::Error> {
+//! use telegraph_rs::{builder::*, nodes};
+//!
+//! let content = nodes![h3().text("Hello"), p().text("Hello, ").child(b().text("world"))];
+//! let page = telegraph.create_page("title", &content, false).await?;
+//! # Ok(())
+//! # }
+//! ```
+pub mod builder;
+#[cfg(feature = "upload-cache")]
+pub mod cache;
+pub mod convert;
 pub mod error;
+#[cfg(feature = "markdown")]
+pub mod markdown;
+#[cfg(feature = "upload")]
+pub mod media;
+#[cfg(feature = "upload")]
+pub mod metadata;
+pub mod rate_limit;
+pub mod render;
+pub mod retry;
+pub mod sanitize;
+pub mod split;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "image-processing")]
+pub mod transcode;
 pub mod types;
+#[cfg(feature = "upload")]
+pub mod upload;
 pub mod utils;
+pub mod validate;
+pub mod views;
 
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+pub use convert::convert_html;
 pub use error::*;
-use kuchikiki::{ElementData, NodeData, NodeRef, traits::TendrilSink};
+#[cfg(feature = "kuchiki")]
+use kuchikiki::{traits::TendrilSink, ElementData, NodeData, NodeRef};
+#[cfg(feature = "markdown")]
+pub use markdown::markdown_to_nodes;
+#[cfg(feature = "upload")]
+pub use media::{LocalizedMedia, MediaOptions};
+pub use rate_limit::{Quota, RateLimit, RateLimiter, RequestKind};
+pub use render::{
+    nodes_to_html, nodes_to_html_with, nodes_to_markdown, nodes_to_summary, nodes_to_text,
+    nodes_to_text_with,
+};
+pub use retry::{RetryEvent, RetryPolicy};
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+pub use sanitize::sanitize_html;
+pub use sanitize::SanitizePolicy;
 pub use types::*;
+#[cfg(feature = "upload")]
+pub use upload::{UploadEvent, UploadOptions, UploadProgress, UploadStream};
+#[cfg(any(feature = "upload", feature = "html"))]
 pub use utils::*;
+pub use validate::{validate, Violation, ViolationKind, MAX_CONTENT_SIZE};
+pub use views::{Granularity, ViewsPoint, ViewsQuery, ViewsSeries};
 
-use reqwest::{
-    multipart::{Form, Part},
-    Client, Response,
+use chrono::NaiveDate;
+use futures_util::{
+    stream::{self, Stream},
+    StreamExt, TryStreamExt,
 };
-use std::{collections::HashMap, fs::File, io::Read, path::Path};
+#[cfg(feature = "upload")]
+use reqwest::multipart::Form;
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Default host of the Telegraph API.
+pub const DEFAULT_API_URL: &str = "https://api.telegra.ph";
+/// Default host of the Telegraph upload endpoint.
+pub const DEFAULT_UPLOAD_URL: &str = "https://telegra.ph";
+/// Maximum size of a file accepted by the upload endpoint.
+pub const MAX_UPLOAD_SIZE: u64 = 5 * 1024 * 1024;
+/// Maximum `limit` accepted by getPageList.
+pub const MAX_PAGE_LIST_LIMIT: i32 = 200;
+
+/// Bytes kept free in each part of a long page for the links to the other parts.
+const PART_LINKS_SIZE: usize = 1024;
+
+/// Number of getViews requests in flight when collecting a [`ViewsSeries`].
+const VIEWS_CONCURRENCY: usize = 4;
+
 macro_rules! send {
     ($e:expr) => {
         $e.send().await.and_then(Response::error_for_status)
     };
 }
 
+/// How to reach the server, shared by [`AccountBuilder`] and [`Telegraph`].
+#[derive(Debug, Clone)]
+struct Api {
+    client: Client,
+    api_url: String,
+    upload_url: String,
+    #[cfg(feature = "upload")]
+    strip_metadata: bool,
+    #[cfg(feature = "upload")]
+    upload_progress: Option<upload::ProgressHook>,
+    #[cfg(feature = "image-processing")]
+    image_options: transcode::ImageOptions,
+    #[cfg(feature = "upload-cache")]
+    upload_cache: Option<std::sync::Arc<dyn cache::UploadCache>>,
+    retry: Option<RetryPolicy>,
+    rate_limiter: Option<RateLimiter>,
+    global_rate_limiter: Option<RateLimiter>,
+}
+
+impl Default for Api {
+    fn default() -> Self {
+        Api {
+            client: Client::new(),
+            api_url: DEFAULT_API_URL.to_owned(),
+            upload_url: DEFAULT_UPLOAD_URL.to_owned(),
+            #[cfg(feature = "upload")]
+            strip_metadata: false,
+            #[cfg(feature = "upload")]
+            upload_progress: None,
+            #[cfg(feature = "image-processing")]
+            image_options: transcode::ImageOptions::default(),
+            #[cfg(feature = "upload-cache")]
+            upload_cache: None,
+            retry: None,
+            rate_limiter: None,
+            global_rate_limiter: None,
+        }
+    }
+}
+
+impl Api {
+    fn url(&self, method: &str) -> String {
+        format!("{}/{}", self.api_url, method)
+    }
+
+    /// Wait until both the account and the global rate limiter allow a `method` request.
+    async fn throttle(&self, method: &str) {
+        let kind = RequestKind::of(method);
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(kind).await;
+        }
+        if let Some(limiter) = &self.global_rate_limiter {
+            limiter.acquire(kind).await;
+        }
+    }
+
+    /// Send the request built by `build`, retrying it according to the retry policy.
+    async fn request<T, F>(&self, method: &'static str, path: Option<&str>, build: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        retry::execute(self.retry.as_ref(), method, || async {
+            self.throttle(method).await;
+            let response = send!(build(&self.client))?;
+            response
+                .json::<ApiResult<T>>()
+                .await?
+                .into_result(method, path)
+        })
+        .await
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct AccountBuilder {
     access_token: Option<String>,
     short_name: String,
     author_name: Option<String>,
     author_url: Option<String>,
-    client: Client,
+    validate_content: bool,
+    api: Api,
 }
 
 impl AccountBuilder {
@@ -86,7 +238,124 @@ impl AccountBuilder {
 
     /// Client
     pub fn client(mut self, client: Client) -> Self {
-        self.client = client;
+        self.api.client = client;
+        self
+    }
+
+    /// Base URL of the Telegraph API, `https://api.telegra.ph` by default.
+    ///
+    /// Useful for mirrors such as `https://api.graph.org` or a local test server.
+    pub fn api_url(mut self, api_url: &str) -> Self {
+        self.api.api_url = api_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Base URL of the upload endpoint, `https://telegra.ph` by default.
+    ///
+    /// Files are posted to `{upload_url}/upload`.
+    pub fn upload_url(mut self, upload_url: &str) -> Self {
+        self.api.upload_url = upload_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Remove EXIF, XMP and IPTC metadata from uploaded JPEG and PNG files with
+    /// [`metadata::strip_metadata`], reporting it in [`ImageInfo::metadata`].
+    ///
+    /// Images with a non-default EXIF orientation are rotated with the `image-processing`
+    /// feature. Without it, they keep a new EXIF block holding only the orientation, see
+    /// [`MetadataReport`](metadata::MetadataReport).
+    ///
+    /// Files that can't be read without being consumed, such as an [`UploadReader`],
+    /// fail with [`Error::CannotStripMetadata`] instead of being sent as is.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "upload")]
+    pub fn strip_metadata(mut self, strip_metadata: bool) -> Self {
+        self.api.strip_metadata = strip_metadata;
+        self
+    }
+
+    /// Call `on_upload_progress` as files are uploaded, to show progress bars or notice
+    /// stalled uploads.
+    ///
+    /// ```
+    /// use telegraph_rs::{AccountBuilder, UploadEvent};
+    ///
+    /// let builder = AccountBuilder::new("bot").on_upload_progress(|event| {
+    ///     if let UploadEvent::Sent(progress) = event {
+    ///         eprintln!("{}: {}/{:?}", progress.file_name, progress.sent, progress.size);
+    ///     }
+    /// });
+    /// ```
+    #[cfg(feature = "upload")]
+    pub fn on_upload_progress<F>(mut self, on_upload_progress: F) -> Self
+    where
+        F: Fn(&UploadEvent) + Send + Sync + 'static,
+    {
+        self.api.upload_progress = Some(upload::ProgressHook::new(on_upload_progress));
+        self
+    }
+
+    /// Convert uploaded images with `options`, see [`transcode`].
+    ///
+    /// Images the upload endpoint would reject are converted, and with a
+    /// [`max_dimension`](transcode::ImageOptions::max_dimension) every image that can
+    /// be decoded is downscaled to it.
+    ///
+    /// [`ImageOptions::default`](transcode::ImageOptions) by default.
+    #[cfg(feature = "image-processing")]
+    pub fn image_options(mut self, options: transcode::ImageOptions) -> Self {
+        self.api.image_options = options;
+        self
+    }
+
+    /// Look uploaded files up in `cache` by content, and only upload those not found.
+    ///
+    /// Files that can't be read without being consumed, such as an [`UploadReader`], are
+    /// always uploaded. With [`strip_metadata`](Self::strip_metadata), the stripped
+    /// content is looked up.
+    ///
+    /// Pass an [`Arc`](std::sync::Arc) to keep a handle on the cache, for instance to
+    /// invalidate entries with [`UploadCache::remove`](cache::UploadCache::remove).
+    ///
+    /// No cache is used by default.
+    #[cfg(feature = "upload-cache")]
+    pub fn upload_cache<C: cache::UploadCache + 'static>(mut self, cache: C) -> Self {
+        self.api.upload_cache = Some(std::sync::Arc::new(cache));
+        self
+    }
+
+    /// Retry failed requests of every endpoint, including uploads, according to `policy`.
+    ///
+    /// Page and account changes that the server may have applied are not retried unless
+    /// enabled by [`RetryPolicy::retry_non_idempotent`].
+    ///
+    /// Requests are not retried by default.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.api.retry = Some(policy);
+        self
+    }
+
+    /// Limit the requests of this account, including those of every clone of the created [`Telegraph`].
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.api.rate_limiter = Some(RateLimiter::new(limit));
+        self
+    }
+
+    /// Limit the requests of this account by a limiter shared with other accounts.
+    ///
+    /// Applied in addition to [`rate_limit`](Self::rate_limit).
+    pub fn global_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.api.global_rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Check content with [`validate`] before creating or editing pages,
+    /// failing with [`Error::InvalidContent`] instead of sending invalid content.
+    ///
+    /// Disabled by default.
+    pub fn validate_content(mut self, validate_content: bool) -> Self {
+        self.validate_content = validate_content;
         self
     }
 
@@ -95,57 +364,99 @@ impl AccountBuilder {
     /// Otherwise import the existing account.
     pub async fn create(mut self) -> Result<Telegraph> {
         if self.access_token.is_none() {
-            let account = Telegraph::create_account(
-                &self.short_name,
-                self.author_name.as_deref(),
-                self.author_url.as_deref(),
-            )
-            .await?;
+            let account = Telegraph::create_account(&self).await?;
             self.access_token = Some(account.access_token.unwrap());
         }
 
         Ok(Telegraph {
-            client: self.client,
             access_token: self.access_token.unwrap(),
             short_name: self.short_name.to_owned(),
             author_name: self.author_name.unwrap_or(self.short_name),
             author_url: self.author_url,
+            validate_content: self.validate_content,
+            api: self.api,
         })
     }
 
     /// Edit info of an an existing account.
     pub async fn edit(self) -> Result<Telegraph> {
-        let response = send!(Client::new()
-            .get("https://api.telegra.ph/editAccountInfo")
-            .query(&[
-                ("access_token", self.access_token.as_ref().unwrap()),
-                ("short_name", &self.short_name),
-                ("author_name", self.author_name.as_ref().unwrap()),
-                (
-                    "author_url",
-                    self.author_url.as_ref().unwrap_or(&String::new()),
-                ),
-            ]))?;
-        let json: Result<Account> = response.json::<ApiResult<Account>>().await?.into();
-        let json = json?;
+        let json: Account = self
+            .api
+            .request("editAccountInfo", None, |client| {
+                client.get(self.api.url("editAccountInfo")).query(&[
+                    ("access_token", self.access_token.as_ref().unwrap()),
+                    ("short_name", &self.short_name),
+                    ("author_name", self.author_name.as_ref().unwrap()),
+                    (
+                        "author_url",
+                        self.author_url.as_ref().unwrap_or(&String::new()),
+                    ),
+                ])
+            })
+            .await?;
 
         Ok(Telegraph {
-            client: Client::new(),
             access_token: self.access_token.unwrap(),
             short_name: json.short_name.clone().unwrap(),
             author_name: json.author_name.or(json.short_name).unwrap(),
             author_url: json.author_url,
+            validate_content: self.validate_content,
+            api: self.api,
         })
     }
 }
 
+/// A getAccountInfo request, created by [`Telegraph::account_info`].
+#[derive(Debug, Clone)]
+pub struct AccountInfoRequest<'a> {
+    telegraph: &'a Telegraph,
+    fields: Vec<AccountField>,
+}
+
+impl AccountInfoRequest<'_> {
+    /// Request `field`.
+    pub fn field(mut self, field: AccountField) -> Self {
+        if !self.fields.contains(&field) {
+            self.fields.push(field);
+        }
+        self
+    }
+
+    /// Request every field of `fields`.
+    pub fn fields<I: IntoIterator<Item = AccountField>>(self, fields: I) -> Self {
+        fields.into_iter().fold(self, Self::field)
+    }
+
+    /// Send the request.
+    pub async fn send(self) -> Result<AccountInfo> {
+        let fields = if self.fields.is_empty() {
+            AccountField::DEFAULT.to_vec()
+        } else {
+            self.fields
+        };
+        let telegraph = self.telegraph;
+        let fields_json = serde_json::to_string(&fields).unwrap();
+        let account = telegraph
+            .api
+            .request("getAccountInfo", None, |client| {
+                client.get(telegraph.api.url("getAccountInfo")).query(&[
+                    ("access_token", &telegraph.access_token),
+                    ("fields", &fields_json),
+                ])
+            })
+            .await?;
+        Ok(AccountInfo::new(account, fields))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Telegraph {
-    client: Client,
     access_token: String,
     short_name: String,
     author_name: String,
     author_url: Option<String>,
+    validate_content: bool,
+    api: Api,
 }
 
 impl Telegraph {
@@ -166,82 +477,93 @@ impl Telegraph {
     /// # Ok(())
     /// # }
     /// ```
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(short_name: &str) -> AccountBuilder {
-        AccountBuilder {
-            short_name: short_name.to_owned(),
-            ..Default::default()
-        }
+        AccountBuilder::new(short_name)
     }
 
-    pub(crate) async fn create_account<'a, S, T>(
-        short_name: &str,
-        author_name: S,
-        author_url: T,
-    ) -> Result<Account>
-    where
-        T: Into<Option<&'a str>>,
-        S: Into<Option<&'a str>>,
-    {
+    pub(crate) async fn create_account(builder: &AccountBuilder) -> Result<Account> {
         let mut params = HashMap::new();
-        params.insert("short_name", short_name);
-        if let Some(author_name) = author_name.into() {
+        params.insert("short_name", builder.short_name.as_str());
+        if let Some(author_name) = &builder.author_name {
             params.insert("author_name", author_name);
         }
-        if let Some(author_url) = author_url.into() {
+        if let Some(author_url) = &builder.author_url {
             params.insert("author_url", author_url);
         }
-        let response = send!(Client::new()
-            .get("https://api.telegra.ph/createAccount")
-            .query(&params))?;
-        response.json::<ApiResult<Account>>().await?.into()
+        builder
+            .api
+            .request("createAccount", None, |client| {
+                client.get(builder.api.url("createAccount")).query(&params)
+            })
+            .await
     }
 
     /// Use this method to create a new Telegraph page. On success, returns a Page object.
     ///
     /// if `return_content` is true, a content field will be returned in the Page object.
     ///
+    /// `content` can be built with [`html_to_nodes`] or the [`builder`] module.
+    ///
     /// ```
+    /// # #[cfg(any(feature = "kuchiki", feature = "html"))]
     /// # async fn test() -> Result<(), telegraph_rs::Error> {
-    /// use telegraph_rs::{Telegraph, html_to_node};
+    /// use telegraph_rs::{Telegraph, html_to_nodes};
     ///
     /// let telegraph = Telegraph::new("author")
     ///     .access_token("b968da509bb76866c35425099bc0989a5ec3b32997d55286c657e6994bbb")
     ///     .create()
     ///     .await?;
     ///
-    /// let page = telegraph.create_page("title", &html_to_node("<p>Hello, world!</p>"), false).await?;
+    /// let page = telegraph.create_page("title", &html_to_nodes("<p>Hello, world!</p>"), false).await?;
     /// # Ok(())
     /// # }
     /// ```
     pub async fn create_page(
         &self,
         title: &str,
-        content: &str,
+        content: &[Node],
         return_content: bool,
     ) -> Result<Page> {
-        let response = send!(self
-            .client
-            .post("https://api.telegra.ph/createPage")
-            .form(&[
-                ("access_token", &*self.access_token),
-                ("title", title),
-                ("author_name", &*self.author_name),
-                ("author_url", self.author_url.as_deref().unwrap_or("")),
-                ("content", content),
-                ("return_content", &*return_content.to_string()),
-            ]))?;
-        response.json::<ApiResult<Page>>().await?.into()
+        self.check_content(content)?;
+        let content = serde_json::to_string(content).unwrap();
+        self.api
+            .request("createPage", None, |client| {
+                client.post(self.api.url("createPage")).form(&[
+                    ("access_token", &*self.access_token),
+                    ("title", title),
+                    ("author_name", &*self.author_name),
+                    ("author_url", self.author_url.as_deref().unwrap_or("")),
+                    ("content", &content),
+                    ("return_content", &*return_content.to_string()),
+                ])
+            })
+            .await
+    }
+
+    /// Validate `content` if enabled by [`AccountBuilder::validate_content`].
+    fn check_content(&self, content: &[Node]) -> Result<()> {
+        if !self.validate_content {
+            return Ok(());
+        }
+        let violations = validate(content);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidContent(violations))
+        }
     }
 
+    #[cfg(feature = "kuchiki")]
     pub async fn create_page_doms(
         &self,
         title: &str,
         content: impl Iterator<Item = NodeRef>,
-        return_content: bool) -> Result<Page> {
-            let nodes = doms_to_nodes(content);
-            let content = serde_json::to_string(&nodes).unwrap();
-            self.create_page(title, &content, return_content).await
-        }
+        return_content: bool,
+    ) -> Result<Page> {
+        let nodes = doms_to_node_list(content);
+        self.create_page(title, &nodes, return_content).await
+    }
 
     /// Use this method to update information about a Telegraph account.
     ///
@@ -254,7 +576,8 @@ impl Telegraph {
             short_name: self.short_name,
             author_name: Some(self.author_name),
             author_url: self.author_url,
-            client: self.client,
+            validate_content: self.validate_content,
+            api: self.api,
         }
     }
 
@@ -265,44 +588,143 @@ impl Telegraph {
         &self,
         path: &str,
         title: &str,
-        content: &str,
+        content: &[Node],
         return_content: bool,
     ) -> Result<Page> {
-        let response = send!(self.client.post("https://api.telegra.ph/editPage").form(&[
-            ("access_token", &*self.access_token),
-            ("path", path),
-            ("title", title),
-            ("author_name", &*self.author_name),
-            ("author_url", self.author_url.as_deref().unwrap_or("")),
-            ("content", content),
-            ("return_content", &*return_content.to_string()),
-        ]))?;
-        response.json::<ApiResult<Page>>().await?.into()
+        self.check_content(content)?;
+        let content = serde_json::to_string(content).unwrap();
+        self.api
+            .request("editPage", Some(path), |client| {
+                client.post(self.api.url("editPage")).form(&[
+                    ("access_token", &*self.access_token),
+                    ("path", path),
+                    ("title", title),
+                    ("author_name", &*self.author_name),
+                    ("author_url", self.author_url.as_deref().unwrap_or("")),
+                    ("content", &content),
+                    ("return_content", &*return_content.to_string()),
+                ])
+            })
+            .await
+    }
+
+    /// Create as many pages as needed to hold `content`, which may exceed [`MAX_CONTENT_SIZE`].
+    ///
+    /// `content` is split at block boundaries with [`split::split_content`]. When it takes
+    /// several pages, they are titled `{title} (Part i/n)` and end with links to the previous
+    /// and next parts. They are created from the last to the first, so that each page links
+    /// to the next one from the start, then every part but the first is edited to link to
+    /// the previous one.
+    ///
+    /// A list split across pages restarts its numbering on each of them, as Telegraph
+    /// doesn't allow the `start` attribute.
+    ///
+    /// Returns the created pages in order.
+    pub async fn create_long_page(&self, title: &str, content: &[Node]) -> Result<Vec<Page>> {
+        let parts = split::split_content(content, MAX_CONTENT_SIZE - PART_LINKS_SIZE);
+        self.publish_parts(&[], title, &parts).await
+    }
+
+    /// Replace the content of a chain of pages created by [`create_long_page`](Self::create_long_page)
+    /// with `content`, re-flowing it across the chain.
+    ///
+    /// Pages are added at the end of the chain if `content` needs more of them. Pages no longer
+    /// needed cannot be deleted, so they are emptied and link to the first page instead.
+    ///
+    /// Returns the pages of the new chain in order.
+    pub async fn edit_long_page(
+        &self,
+        paths: &[&str],
+        title: &str,
+        content: &[Node],
+    ) -> Result<Vec<Page>> {
+        let parts = split::split_content(content, MAX_CONTENT_SIZE - PART_LINKS_SIZE);
+        let pages = self.publish_parts(paths, title, &parts).await?;
+
+        let first = &pages[0];
+        for path in paths.iter().skip(pages.len()) {
+            let stub = nodes![builder::p()
+                .text("This part was merged into ")
+                .child(builder::a(&first.url).text(title))
+                .text(".")];
+            self.edit_page(path, title, &stub, false).await?;
+        }
+        Ok(pages)
+    }
+
+    /// Publish each part of a long page, editing the page at the same index of `paths` or
+    /// creating one, and link the parts to each other.
+    ///
+    /// Parts are published from the last to the first with a link to the next one, then
+    /// edited once the previous one is known.
+    async fn publish_parts(
+        &self,
+        paths: &[&str],
+        title: &str,
+        parts: &[Vec<Node>],
+    ) -> Result<Vec<Page>> {
+        let mut pages: Vec<Page> = vec![];
+        for (i, part) in parts.iter().enumerate().rev() {
+            let content = with_part_links(part, i, None, pages.last());
+            let part_title = part_title(title, i, parts.len());
+            let page = match paths.get(i) {
+                Some(path) => self.edit_page(path, &part_title, &content, false).await?,
+                None => self.create_page(&part_title, &content, false).await?,
+            };
+            pages.push(page);
+        }
+        pages.reverse();
+        for i in 1..pages.len() {
+            let content = with_part_links(&parts[i], i, Some(&pages[i - 1]), pages.get(i + 1));
+            let part_title = part_title(title, i, parts.len());
+            pages[i] = self
+                .edit_page(&pages[i].path, &part_title, &content, false)
+                .await?;
+        }
+        Ok(pages)
     }
 
     /// Use this method to get information about a Telegraph account. Returns an Account object on success.
     ///
-    /// Available fields: short_name, author_name, author_url, auth_url, page_count.
-    pub async fn get_account_info(&self, fields: &[&str]) -> Result<Account> {
-        let response = send!(self
-            .client
-            .get("https://api.telegra.ph/getAccountInfo")
-            .query(&[
-                ("access_token", &self.access_token),
-                ("fields", &serde_json::to_string(fields).unwrap()),
-            ]))?;
-        response.json::<ApiResult<Account>>().await?.into()
+    /// If `fields` is empty, short_name, author_name and author_url are returned.
+    pub async fn get_account_info(&self, fields: &[AccountField]) -> Result<AccountInfo> {
+        self.account_info()
+            .fields(fields.iter().copied())
+            .send()
+            .await
+    }
+
+    /// Build a getAccountInfo request.
+    ///
+    /// ```
+    /// # async fn run(telegraph: telegraph_rs::Telegraph) -> Result<(), telegraph_rs::Error> {
+    /// use telegraph_rs::AccountField;
+    ///
+    /// let info = telegraph
+    ///     .account_info()
+    ///     .fields([AccountField::ShortName, AccountField::PageCount])
+    ///     .send()
+    ///     .await?;
+    /// println!("{} has {} pages", info.short_name()?, info.page_count()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn account_info(&self) -> AccountInfoRequest<'_> {
+        AccountInfoRequest {
+            telegraph: self,
+            fields: vec![],
+        }
     }
 
     /// Use this method to get a Telegraph page. Returns a Page object on success.
-    pub async fn get_page(path: &str, return_content: bool) -> Result<Page> {
-        let response = Client::new()
-            .get(&format!("https://api.telegra.ph/getPage/{}", path))
-            .query(&[("return_content", return_content.to_string())])
-            .send()
-            .await?
-            .error_for_status()?;
-        response.json::<ApiResult<Page>>().await?.into()
+    pub async fn page(&self, path: &str, return_content: bool) -> Result<Page> {
+        self.api
+            .request("getPage", Some(path), |client| {
+                client
+                    .get(self.api.url(&format!("getPage/{}", path)))
+                    .query(&[("return_content", return_content.to_string())])
+            })
+            .await
     }
 
     /// Use this method to get a list of pages belonging to a Telegraph account.
@@ -312,42 +734,127 @@ impl Telegraph {
     /// - `offset` Sequential number of the first page to be returned. (suggest: 0)
     /// - `limit` Limits the number of pages to be retrieved. (suggest: 50)
     pub async fn get_page_list(&self, offset: i32, limit: i32) -> Result<PageList> {
-        let response = send!(self
-            .client
-            .get("https://api.telegra.ph/getPageList")
-            .query(&[
-                ("access_token", &self.access_token),
-                ("offset", &offset.to_string()),
-                ("limit", &limit.to_string()),
-            ]))?;
-        response.json::<ApiResult<PageList>>().await?.into()
+        self.api
+            .request("getPageList", None, |client| {
+                client.get(self.api.url("getPageList")).query(&[
+                    ("access_token", &self.access_token),
+                    ("offset", &offset.to_string()),
+                    ("limit", &limit.to_string()),
+                ])
+            })
+            .await
+    }
+
+    /// Stream every page of the account, most recently created first.
+    ///
+    /// Pages are fetched lazily, [`MAX_PAGE_LIST_LIMIT`] at a time, until `total_count` is reached.
+    /// The returned pages have no content.
+    ///
+    /// ```
+    /// # async fn run(telegraph: telegraph_rs::Telegraph) -> Result<(), telegraph_rs::Error> {
+    /// use futures_util::TryStreamExt;
+    ///
+    /// let pages: Vec<_> = telegraph.pages().try_collect().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pages(&self) -> impl Stream<Item = Result<Page>> + '_ {
+        self.page_stream(MAX_PAGE_LIST_LIMIT)
+    }
+
+    /// Like [`pages`](Self::pages), but also fetch the content of each page,
+    /// with at most `concurrency` getPage requests in flight.
+    pub fn pages_with_content(&self, concurrency: usize) -> impl Stream<Item = Result<Page>> + '_ {
+        self.pages()
+            .map(move |page| async move { self.page(&page?.path, true).await })
+            .buffered(concurrency.max(1))
+    }
+
+    fn page_stream(&self, limit: i32) -> impl Stream<Item = Result<Page>> + '_ {
+        stream::try_unfold(Some(0), move |offset| async move {
+            let offset = match offset {
+                Some(offset) => offset,
+                None => return Ok::<_, Error>(None),
+            };
+            let list = self.get_page_list(offset, limit).await?;
+            let next = offset + list.pages.len() as i32;
+            let next = (!list.pages.is_empty() && next < list.total_count).then_some(next);
+            let pages = stream::iter(list.pages.into_iter().map(Ok::<_, Error>));
+            Ok(Some((pages, next)))
+        })
+        .try_flatten()
     }
 
     /// Use this method to get the number of views for a Telegraph article.
     ///
     /// Returns a PageViews object on success.
     ///
-    /// By default, the total number of page views will be returned.
+    /// The query is validated before being sent, see [`ViewsQuery::validate`].
     ///
     /// ```rust
     /// # async fn run() -> Result<(), telegraph_rs::Error> {
-    /// use telegraph_rs::Telegraph;
+    /// use telegraph_rs::{Telegraph, ViewsQuery};
+    ///
+    /// let telegraph = Telegraph::new("short_name")
+    ///     .access_token("b968da509bb76866c35425099bc0989a5ec3b32997d55286c657e6994bbb")
+    ///     .create()
+    ///     .await?;
     ///
-    /// let view1 = Telegraph::get_views("Sample-Page-12-15", &vec![2016, 12]).await?;
-    /// let view2 = Telegraph::get_views("Sample-Page-12-15", &vec![2019, 5, 19, 12]).await?; // year-month-day-hour
+    /// let total = telegraph.views("Sample-Page-12-15", ViewsQuery::Total).await?;
+    /// let view1 = telegraph.views("Sample-Page-12-15", ViewsQuery::month(2016, 12)).await?;
+    /// let view2 = telegraph.views("Sample-Page-12-15", ViewsQuery::hour(2019, 5, 19, 12)).await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn get_views(path: &str, time: &[i32]) -> Result<PageViews> {
-        let params = ["year", "month", "day", "hour"]
-            .iter()
-            .zip(time)
-            .collect::<HashMap<_, _>>();
+    pub async fn views(&self, path: &str, query: ViewsQuery) -> Result<PageViews> {
+        query.validate()?;
+        let params = query.params();
+
+        self.api
+            .request("getViews", Some(path), |client| {
+                client
+                    .get(self.api.url(&format!("getViews/{}", path)))
+                    .query(&params)
+            })
+            .await
+    }
 
-        let response = send!(Client::new()
-            .get(&format!("https://api.telegra.ph/getViews/{}", path))
-            .query(&params))?;
-        response.json::<ApiResult<PageViews>>().await?.into()
+    /// Collect the views of the page at `path` for every period of `granularity`
+    /// between `from` and `to`, both included.
+    ///
+    /// ```rust
+    /// # async fn run(telegraph: telegraph_rs::Telegraph) -> Result<(), telegraph_rs::Error> {
+    /// use chrono::NaiveDate;
+    /// use telegraph_rs::Granularity;
+    ///
+    /// let from = NaiveDate::from_ymd_opt(2019, 5, 1).unwrap();
+    /// let to = NaiveDate::from_ymd_opt(2019, 5, 31).unwrap();
+    /// let series = telegraph.views_series("Sample-Page-12-15", Granularity::Day, from, to).await?;
+    /// std::fs::write("views.csv", series.to_csv())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn views_series(
+        &self,
+        path: &str,
+        granularity: Granularity,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<ViewsSeries> {
+        let periods = granularity.periods(from, to)?;
+        let points = stream::iter(periods)
+            .map(|period| async move {
+                let views = self.views(path, period).await?.views;
+                Ok::<_, Error>(ViewsPoint { period, views })
+            })
+            .buffered(VIEWS_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(ViewsSeries {
+            path: path.to_owned(),
+            granularity,
+            points,
+        })
     }
 
     /// Use this method to revoke access_token and generate a new one,
@@ -358,116 +865,596 @@ impl Telegraph {
     ///
     /// On success, returns an Account object with new access_token and auth_url fields.
     pub async fn revoke_access_token(&mut self) -> Result<Account> {
-        let response = send!(self
-            .client
-            .get("https://api.telegra.ph/revokeAccessToken")
-            .query(&[("access_token", &self.access_token)]))?;
-        let json: Result<Account> = response.json::<ApiResult<Account>>().await?.into();
-        if json.is_ok() {
-            self.access_token = json
-                .as_ref()
-                .unwrap()
-                .access_token
-                .as_ref()
-                .unwrap()
-                .to_owned();
+        let json = self
+            .api
+            .request("revokeAccessToken", None, |client| {
+                client
+                    .get(self.api.url("revokeAccessToken"))
+                    .query(&[("access_token", &self.access_token)])
+            })
+            .await;
+        if let Ok(Account {
+            access_token: Some(access_token),
+            ..
+        }) = &json
+        {
+            self.access_token = access_token.to_owned();
         }
         json
     }
 
     /// Upload files to telegraph with custom client
     #[cfg(feature = "upload")]
-    pub async fn upload_with<T: Uploadable>(
+    pub async fn upload_media_with<T: Uploadable>(
+        &self,
         files: &[T],
         client: &Client,
     ) -> Result<Vec<ImageInfo>> {
-        let mut form = Form::new();
-        for (i, file) in files.iter().enumerate() {
-            let part = file.part()?;
-            form = form.part(i.to_string(), part);
-        }
-        let response = send!(client.post("https://telegra.ph/upload").multipart(form))?;
+        let files: Vec<_> = files.iter().collect();
+        self.send_files(&files, client).await
+    }
 
-        match response.json::<UploadResult>().await? {
-            UploadResult::Error { error } => Err(Error::ApiError(error)),
-            UploadResult::Source(v) => Ok(v),
+    /// Check `file` before uploading it.
+    #[cfg(feature = "upload")]
+    async fn check<T: Uploadable>(&self, file: &T, options: &UploadOptions) -> Result<()> {
+        let probe = file.probe().await?;
+        // Whether a converted file fits is unknown until it is converted.
+        #[cfg(feature = "image-processing")]
+        if transcode::needs_conversion(&probe, &self.api.image_options) {
+            let probe = upload::Probe {
+                head: None,
+                ..probe
+            };
+            return probe.check(self.api.image_options.max_input_size);
         }
+        probe.check(options.max_size)
     }
 
-    /// Upload files to telegraph
+    /// Upload `files` in a single request, removing their metadata if enabled.
     #[cfg(feature = "upload")]
-    pub async fn upload<T: Uploadable>(files: &[T]) -> Result<Vec<ImageInfo>> {
-        Self::upload_with(files, &Client::new()).await
+    async fn send_files<T: Uploadable>(
+        &self,
+        files: &[&T],
+        client: &Client,
+    ) -> Result<Vec<ImageInfo>> {
+        if !self.api.strip_metadata {
+            return self.post_uncached(files, client).await;
+        }
+        let mut stripped = vec![];
+        let mut reports = vec![];
+        for file in files {
+            let file = file.to_bytes().await?.ok_or(Error::CannotStripMetadata)?;
+            let (file, report) =
+                match tokio::task::spawn_blocking(move || file.strip_metadata()).await {
+                    Ok(result) => result?,
+                    Err(e) => std::panic::resume_unwind(e.into_panic()),
+                };
+            stripped.push(file);
+            reports.push(report);
+        }
+        let stripped: Vec<_> = stripped.iter().collect();
+        let mut images = self.post_uncached(&stripped, client).await?;
+        for (image, report) in images.iter_mut().zip(reports) {
+            image.metadata = Some(report);
+        }
+        Ok(images)
     }
-}
 
-#[cfg(feature = "html")]
-fn html_to_node_inner(node: &html_parser::Node) -> Option<Node> {
-    match node {
-        html_parser::Node::Text(text) => Some(Node::Text(text.to_owned())),
-        html_parser::Node::Element(element) => Some(Node::NodeElement(NodeElement {
-            tag: element.name.to_owned(),
-            attrs: {
-                (!element.attributes.is_empty()).then(|| element.attributes.clone())
-            },
-            children: {
-                if element.children.is_empty() {
-                    None
-                } else {
-                    element.children.iter().map(|node| html_to_node_inner(node))
-                        .collect::<Option<Vec<_>>>()
+    /// Post the `files` missing from the upload cache, if enabled.
+    #[cfg(feature = "upload")]
+    async fn post_uncached<T: Uploadable>(
+        &self,
+        files: &[&T],
+        client: &Client,
+    ) -> Result<Vec<ImageInfo>> {
+        #[cfg(feature = "upload-cache")]
+        if let Some(cache) = &self.api.upload_cache {
+            let mut keys = vec![];
+            for file in files {
+                keys.push(cache_key(*file).await?);
+            }
+            let cached = keys.clone();
+            let srcs = with_cache(cache, move |cache| {
+                cached
+                    .iter()
+                    .map(|key| match key {
+                        Some(key) => cache.get(key),
+                        None => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await?;
+            let mut images: Vec<_> = srcs
+                .into_iter()
+                .map(|src| {
+                    src.map(|src| ImageInfo {
+                        src,
+                        metadata: None,
+                    })
+                })
+                .collect();
+            let missing: Vec<_> = files
+                .iter()
+                .zip(&images)
+                .filter(|(_, image)| image.is_none())
+                .map(|(file, _)| *file)
+                .collect();
+            if !missing.is_empty() {
+                let mut uploaded = self.post_files(&missing, client).await?.into_iter();
+                let mut entries = vec![];
+                for (image, key) in images.iter_mut().zip(&keys) {
+                    if image.is_some() {
+                        continue;
+                    }
+                    let info = uploaded
+                        .next()
+                        .ok_or_else(|| Error::api("EMPTY_RESPONSE".to_owned(), "upload", None))?;
+                    if let Some(key) = key {
+                        entries.push((*key, info.src.clone()));
+                    }
+                    *image = Some(info);
                 }
-            },
-        })),
-        _ => None,
+                with_cache(cache, move |cache| cache.insert_all(&entries)).await?;
+            }
+            return Ok(images.into_iter().flatten().collect());
+        }
+        self.post_files(files, client).await
     }
-}
 
-#[cfg(feature = "upload")]
-fn guess_mime<P: AsRef<Path>>(path: P) -> String {
-    let mime = mime_guess::from_path(path).first_or(mime_guess::mime::TEXT_PLAIN);
-    let mut s = format!("{}/{}", mime.type_(), mime.subtype());
-    if let Some(suffix) = mime.suffix() {
-        s.push('+');
-        s.push_str(suffix.as_str());
-    }
-    s
-}
+    /// Post `files` in a single request, reporting its progress if enabled.
+    #[cfg(feature = "upload")]
+    async fn post_files<T: Uploadable>(
+        &self,
+        files: &[&T],
+        client: &Client,
+    ) -> Result<Vec<ImageInfo>> {
+        let tracker = self
+            .api
+            .upload_progress
+            .clone()
+            .map(|hook| upload::Tracker::new(hook, files.len()));
+        #[cfg(feature = "image-processing")]
+        let converted = convert_images(files, &self.api.image_options).await?;
+        #[cfg(not(feature = "image-processing"))]
+        let converted: Vec<Option<UploadBytes>> = vec![];
+        retry::execute(self.api.retry.as_ref(), "upload", || async {
+            self.api.throttle("upload").await;
+            let tracker = tracker.as_ref().map(upload::Tracker::restart);
+            let mut form = Form::new();
+            for (i, file) in files.iter().enumerate() {
+                let part = match converted.get(i).and_then(Option::as_ref) {
+                    Some(file) => file_part(file, i, tracker.as_ref()).await?,
+                    None => file_part(*file, i, tracker.as_ref()).await?,
+                };
+                form = form.part(i.to_string(), part);
+            }
+            if let Some(tracker) = &tracker {
+                tracker.start();
+            }
+            let result = async {
+                let response = send!(client
+                    .post(format!("{}/upload", self.api.upload_url))
+                    .multipart(form))?;
 
-#[cfg(feature = "upload")]
-fn read_to_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
-    let mut bytes = vec![];
-    let mut file = File::open(path)?;
-    file.read_to_end(&mut bytes)?;
-    Ok(bytes)
-}
+                match response.json::<UploadResult>().await? {
+                    UploadResult::Error { error } => Err(Error::api(error, "upload", None)),
+                    UploadResult::Source(v) => Ok(v),
+                }
+            }
+            .await;
+            if let Some(tracker) = &tracker {
+                tracker.finish(result.is_ok());
+            }
+            result
+        })
+        .await
+    }
 
-/// Parse html to node string
+    /// Upload the media referenced by `content` and point it to the uploaded files.
+    ///
+    /// The `src` of every `img` and `video` may be a `data:` URI or a remote `http(s)`
+    /// or protocol-relative URL, which is downloaded first, or a local path or `file://` URL inside the
+    /// [`local_dir`](MediaOptions::local_dir) of `options`. Sources already on the upload
+    /// host are left alone, and a source used several times is uploaded once.
+    ///
+    /// The files are sent by [`upload_files`](Telegraph::upload_files), so each is checked
+    /// before being sent. A source that can't be parsed, read, downloaded or uploaded is
+    /// left unchanged and reported in [`LocalizedMedia::failed`], the others are still
+    /// rewritten.
+    ///
+    /// ```
+    /// # async fn run(telegraph: telegraph_rs::Telegraph) -> Result<(), telegraph_rs::Error> {
+    /// use telegraph_rs::{builder::*, nodes, MediaOptions};
+    ///
+    /// let mut content = nodes![figure().image("photos/cat.jpg").caption("A cat")];
+    /// let options = MediaOptions::new().local_dir(".");
+    /// let localized = telegraph.localize_media(&mut content, &options).await;
+    /// for (src, error) in &localized.failed {
+    ///     println!("{}: {}", src, error);
+    /// }
+    /// let page = telegraph.create_page("Cat", &content, false).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "upload")]
+    pub async fn localize_media(
+        &self,
+        content: &mut [Node],
+        options: &MediaOptions,
+    ) -> LocalizedMedia {
+        let mut srcs = media::media_srcs(content);
+        let mut sources: Vec<String> = vec![];
+        let mut fetched = vec![];
+        let mut files = vec![];
+        for src in &srcs {
+            if sources.contains(src) {
+                continue;
+            }
+            let file = match media::MediaSource::parse(src, &self.api.upload_url) {
+                Ok(source) => source.fetch(&self.api.client, options).await,
+                Err(e) => Err(e),
+            };
+            sources.push(src.to_string());
+            // Whether a file is to be uploaded for the source.
+            fetched.push(match file {
+                Ok(Some(file)) => {
+                    files.push(file);
+                    Ok(true)
+                }
+                Ok(None) => Ok(false),
+                Err(e) => Err(e),
+            });
+        }
+        let mut uploaded = self.upload_files(&files, &options.upload).await.into_iter();
+        let results: Vec<_> = fetched
+            .into_iter()
+            .map(|fetched| match fetched {
+                Ok(true) => uploaded.next().unwrap().map(|info| Some(info.src)),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect();
+
+        let mut localized = LocalizedMedia::default();
+        for src in &mut srcs {
+            let i = sources.iter().position(|source| source == &**src).unwrap();
+            if let Ok(Some(uploaded)) = &results[i] {
+                **src = uploaded.clone();
+                localized.rewritten += 1;
+            }
+        }
+        for (source, result) in sources.into_iter().zip(results) {
+            if let Err(e) = result {
+                localized.failed.push((source, e));
+            }
+        }
+        localized
+    }
+
+    /// Upload files to telegraph
+    #[cfg(feature = "upload")]
+    pub async fn upload_media<T: Uploadable>(&self, files: &[T]) -> Result<Vec<ImageInfo>> {
+        self.upload_media_with(files, &self.api.client).await
+    }
+
+    /// A client without account reaching the default hosts, for the deprecated
+    /// associated functions.
+    fn anonymous() -> Telegraph {
+        Telegraph {
+            access_token: String::new(),
+            short_name: String::new(),
+            author_name: String::new(),
+            author_url: None,
+            validate_content: false,
+            api: Api::default(),
+        }
+    }
+
+    /// Get a page from `https://api.telegra.ph`.
+    #[deprecated(
+        since = "0.7.0",
+        note = "use the `page` method, which respects `api_url`"
+    )]
+    pub async fn get_page(path: &str, return_content: bool) -> Result<Page> {
+        Self::anonymous().page(path, return_content).await
+    }
+
+    /// Get the views of a page from `https://api.telegra.ph`, `time` being the year,
+    /// month, day and hour, the extra values ignored.
+    ///
+    /// The values are sent as is, without the checks of [`ViewsQuery`].
+    #[deprecated(
+        since = "0.7.0",
+        note = "use the `views` method, which respects `api_url`"
+    )]
+    pub async fn get_views(path: &str, time: &[i32]) -> Result<PageViews> {
+        let params: Vec<_> = ["year", "month", "day", "hour"].iter().zip(time).collect();
+        let api = Api::default();
+        api.request("getViews", Some(path), |client| {
+            client
+                .get(api.url(&format!("getViews/{}", path)))
+                .query(&params)
+        })
+        .await
+    }
+
+    /// Upload files to `https://telegra.ph` with a custom client.
+    #[deprecated(
+        since = "0.7.0",
+        note = "use the `upload_media_with` method, which respects `upload_url`"
+    )]
+    #[cfg(feature = "upload")]
+    pub async fn upload_with<T: Uploadable>(
+        files: &[T],
+        client: &Client,
+    ) -> Result<Vec<ImageInfo>> {
+        Self::anonymous().upload_media_with(files, client).await
+    }
+
+    /// Upload files to `https://telegra.ph`.
+    #[deprecated(
+        since = "0.7.0",
+        note = "use the `upload_media` method, which respects `upload_url`"
+    )]
+    #[cfg(feature = "upload")]
+    pub async fn upload<T: Uploadable>(files: &[T]) -> Result<Vec<ImageInfo>> {
+        Self::anonymous().upload_media(files).await
+    }
+
+    /// Upload files, reporting the result of each one.
+    ///
+    /// Files that are too big or whose content isn't jpeg, png, gif or mp4 are rejected
+    /// without being sent. The others are sent in chunks of [`UploadOptions::chunk_size`]
+    /// files, several chunks at once. When a chunk fails, its files are sent again one
+    /// by one to find out which of them the server rejects; an [`UploadReader`] can't be
+    /// sent again and fails with [`Error::ReaderConsumed`].
+    ///
+    /// Returns one result per file, in the order of `files`.
+    ///
+    /// ```
+    /// # async fn run(telegraph: telegraph_rs::Telegraph) {
+    /// use telegraph_rs::UploadOptions;
+    ///
+    /// let files = ["1.jpg", "2.png", "3.gif"];
+    /// let results = telegraph.upload_files(&files, &UploadOptions::new()).await;
+    /// for (file, result) in files.iter().zip(results) {
+    ///     match result {
+    ///         Ok(image) => println!("{}: {}", file, image.src),
+    ///         Err(e) => println!("{}: {}", file, e),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "upload")]
+    pub async fn upload_files<T: Uploadable + Sync>(
+        &self,
+        files: &[T],
+        options: &UploadOptions,
+    ) -> Vec<Result<ImageInfo>> {
+        let mut results = vec![];
+        for file in files {
+            results.push(self.check(file, options).await.err().map(Err));
+        }
+        let pending: Vec<usize> = (0..files.len()).filter(|i| results[*i].is_none()).collect();
+        let uploaded: Vec<_> = stream::iter(pending.chunks(options.chunk_size))
+            .map(|chunk| async move {
+                let batch: Vec<_> = chunk.iter().map(|i| &files[*i]).collect();
+                let result = self.send_files(&batch, &self.api.client).await;
+                match result {
+                    Ok(images) if images.len() == chunk.len() => chunk
+                        .iter()
+                        .copied()
+                        .zip(images.into_iter().map(Ok))
+                        .collect(),
+                    Err(e) if chunk.len() == 1 => vec![(chunk[0], Err(e))],
+                    _ => {
+                        let mut results = vec![];
+                        for (i, file) in chunk.iter().zip(batch) {
+                            let result = self.send_files(&[file], &self.api.client).await;
+                            let image = result.and_then(|images| {
+                                images.into_iter().next().ok_or_else(|| {
+                                    Error::api("EMPTY_RESPONSE".to_owned(), "upload", None)
+                                })
+                            });
+                            results.push((*i, image));
+                        }
+                        results
+                    }
+                }
+            })
+            .buffered(options.concurrency)
+            .collect()
+            .await;
+        for (i, result) in uploaded.into_iter().flatten() {
+            results[i] = Some(result);
+        }
+        results.into_iter().flatten().collect()
+    }
+}
+
+/// Content hash of `file`, computed on a blocking thread, or `None` if it can't be read
+/// without being consumed and so can't be cached.
+#[cfg(feature = "upload-cache")]
+async fn cache_key<T: Uploadable>(file: &T) -> Result<Option<cache::CacheKey>> {
+    let file = match file.to_bytes().await? {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    let hash = move || cache::CacheKey::of(file.bytes());
+    match tokio::task::spawn_blocking(hash).await {
+        Ok(key) => Ok(Some(key)),
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Run `f` with `cache` on a blocking thread, as caches may read and write files.
+#[cfg(feature = "upload-cache")]
+async fn with_cache<R, F>(cache: &std::sync::Arc<dyn cache::UploadCache>, f: F) -> R
+where
+    R: Send + 'static,
+    F: FnOnce(&dyn cache::UploadCache) -> R + Send + 'static,
+{
+    let cache = cache.clone();
+    match tokio::task::spawn_blocking(move || f(&*cache)).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// The multipart part of the `i`-th file, reporting its progress to `tracker` if any.
+#[cfg(feature = "upload")]
+async fn file_part<T: Uploadable>(
+    file: &T,
+    i: usize,
+    tracker: Option<&upload::Tracker>,
+) -> Result<reqwest::multipart::Part> {
+    match (file.stream().await?, tracker) {
+        (Some(stream), Some(tracker)) => tracker.track(i, stream).into_part(),
+        (Some(stream), None) => stream.into_part(),
+        (None, _) => file.part(),
+    }
+}
+
+/// The `files` that need to be converted with `options`, converted on a blocking thread.
+///
+/// Done once before sending the files, instead of at every attempt.
+#[cfg(feature = "image-processing")]
+async fn convert_images<T: Uploadable>(
+    files: &[&T],
+    options: &transcode::ImageOptions,
+) -> Result<Vec<Option<UploadBytes>>> {
+    let mut converted = vec![];
+    for file in files {
+        let file = if transcode::needs_conversion(&file.probe().await?, options) {
+            file.to_bytes().await?
+        } else {
+            None
+        };
+        let file = match file {
+            Some(file) => {
+                let options = options.clone();
+                let convert = move || transcode::fit_image(&file, &options);
+                match tokio::task::spawn_blocking(convert).await {
+                    Ok(result) => Some(result?),
+                    Err(e) => std::panic::resume_unwind(e.into_panic()),
+                }
+            }
+            None => None,
+        };
+        converted.push(file);
+    }
+    Ok(converted)
+}
+
+/// Title of part `i` of the `n` parts of a long page.
+fn part_title(title: &str, i: usize, n: usize) -> String {
+    if n == 1 {
+        title.to_owned()
+    } else {
+        format!("{} (Part {}/{})", title, i + 1, n)
+    }
+}
+
+/// Part `i` of a long page followed by links to the `prev` and `next` parts, if any.
+fn with_part_links(part: &[Node], i: usize, prev: Option<&Page>, next: Option<&Page>) -> Vec<Node> {
+    let mut content = part.to_vec();
+    if prev.is_none() && next.is_none() {
+        return content;
+    }
+    let mut links = builder::p();
+    if let Some(prev) = prev {
+        links = links.child(builder::a(&prev.url).text(format!("← Part {}", i)));
+    }
+    if let Some(next) = next {
+        if prev.is_some() {
+            links = links.text(" | ");
+        }
+        links = links.child(builder::a(&next.url).text(format!("Part {} →", i + 2)));
+    }
+    content.push(builder::hr().build());
+    content.push(links.build());
+    content
+}
+
+#[cfg(feature = "html")]
+fn html_to_node_inner(node: &html_parser::Node) -> Option<Node> {
+    match node {
+        html_parser::Node::Text(text) => Some(Node::Text(text.to_owned())),
+        html_parser::Node::Element(element) => Some(Node::NodeElement(NodeElement {
+            tag: element.name.to_owned(),
+            attrs: { (!element.attributes.is_empty()).then(|| element.attributes.clone()) },
+            children: {
+                let children: Vec<_> = element
+                    .children
+                    .iter()
+                    .filter_map(html_to_node_inner)
+                    .collect();
+                if children.is_empty() {
+                    None
+                } else {
+                    Some(children)
+                }
+            },
+        })),
+        _ => None,
+    }
+}
+
+/// Parse html to nodes
 ///
 /// ```rust
-/// use telegraph_rs::html_to_node;
+/// use telegraph_rs::html_to_nodes;
 ///
-/// let node = html_to_node("<p>Hello, world</p>");
-/// assert_eq!(node, r#"[{"tag":"p","children":["Hello, world"]}]"#);
+/// let nodes = html_to_nodes("<p>Hello, world</p>");
+/// assert_eq!(serde_json::to_string(&nodes).unwrap(), r#"[{"tag":"p","children":["Hello, world"]}]"#);
 /// ```
 #[cfg(feature = "kuchiki")]
-pub fn html_to_node(html: &str) -> String {
+pub fn html_to_nodes(html: &str) -> Vec<Node> {
     let document = kuchikiki::parse_html().one(html);
     let body = document.last_child().unwrap().last_child().unwrap();
-    let nodes = doms_to_nodes(body.children());
-    serde_json::to_string(&nodes).unwrap()
+    doms_to_node_list(body.children())
 }
 
+/// Parse html to nodes, serialized as JSON.
 #[cfg(feature = "kuchiki")]
-/// Parse the iterator of dom nodes to node structure
+#[deprecated(since = "0.7.0", note = "use `html_to_nodes`, which returns the nodes")]
+pub fn html_to_node(html: &str) -> String {
+    serde_json::to_string(&html_to_nodes(html)).unwrap()
+}
+
+#[cfg(feature = "kuchiki")]
+/// Parse the iterator of dom nodes to node structure, `None` if one of them isn't an
+/// element or text.
+#[deprecated(
+    since = "0.7.0",
+    note = "use `doms_to_node_list`, which skips other nodes"
+)]
 pub fn doms_to_nodes<T>(nodes: T) -> Option<Vec<Node>>
-where T: Iterator<Item = NodeRef> {
-    nodes.map(|node| dom_to_node(&node))
-    .collect()
+where
+    T: Iterator<Item = NodeRef>,
+{
+    nodes.map(|node| dom_to_node(&node)).collect()
+}
+
+#[cfg(feature = "kuchiki")]
+/// Parse the iterator of dom nodes to node structure
+///
+/// Nodes other than elements and text, such as comments, are skipped.
+/// Use [`convert_html`] to know what was left out.
+///
+/// Whitespace is collapsed with [`convert::normalize_whitespace`].
+pub fn doms_to_node_list<T>(nodes: T) -> Vec<Node>
+where
+    T: Iterator<Item = NodeRef>,
+{
+    let nodes: Vec<_> = nodes.filter_map(|node| dom_to_node(&node)).collect();
+    convert::normalize_whitespace(&nodes, false)
 }
 
 #[cfg(feature = "kuchiki")]
 /// Parse the dom node to node structure
+///
+/// Text is kept verbatim.
 pub fn dom_to_node(node: &NodeRef) -> Option<Node> {
     match node.data() {
         NodeData::Text(text) => Some(Node::Text(text.borrow().clone())),
@@ -484,14 +1471,17 @@ pub fn dom_to_node(node: &NodeRef) -> Option<Node> {
             Some(Node::NodeElement(NodeElement {
                 tag: element_data.name.local.to_string(),
                 attrs: element_data_to_attribute(element_data),
-                children: children,
+                children,
             }))
         }
         _ => None,
     }
 }
 
-fn element_data_to_attribute(element_data: &ElementData) -> Option<HashMap<String, Option<String>>> {
+#[cfg(feature = "kuchiki")]
+fn element_data_to_attribute(
+    element_data: &ElementData,
+) -> Option<HashMap<String, Option<String>>> {
     let map = &element_data.attributes.borrow().map;
     if map.is_empty() {
         return None;
@@ -500,13 +1490,12 @@ fn element_data_to_attribute(element_data: &ElementData) -> Option<HashMap<Strin
     let mut attrs = HashMap::new();
     map.iter()
         .filter(|(name, _attr)| {
-            // FIXME: Now the key of function return type is Option<String>, we can
-            // handle empty value as None.
             name.local.eq_str_ignore_ascii_case("href")
                 || name.local.eq_str_ignore_ascii_case("src")
         })
         .for_each(|(name, attr)| {
-            attrs.insert(name.local.to_string(), Some(attr.value.clone()));
+            let value = Some(attr.value.clone()).filter(|value| !value.is_empty());
+            attrs.insert(name.local.to_string(), value);
         });
 
     if attrs.is_empty() {
@@ -518,34 +1507,551 @@ fn element_data_to_attribute(element_data: &ElementData) -> Option<HashMap<Strin
 
 #[cfg(test)]
 mod tests {
-    use crate::Telegraph;
+    use crate::{
+        builder::*,
+        nodes,
+        render::{HtmlOptions, TextOptions},
+        split,
+        testing::{FakeTelegraph, Fault},
+        validate, AccountField, ApiErrorKind, Error, Granularity, Node, NodeElement, Quota,
+        RateLimit, RateLimiter, RequestKind, RetryPolicy, Telegraph, ViewsQuery, Violation,
+        ViolationKind, MAX_CONTENT_SIZE,
+    };
+    use chrono::NaiveDate;
+    use futures_util::TryStreamExt;
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tokio::time::Instant;
 
     #[test]
-    fn html_to_node() {
+    #[cfg(any(feature = "kuchiki", feature = "html"))]
+    fn html_to_nodes() {
         let html = r#"<a>Text</a><p>img:<img src="https://me"></p>"#;
-        println!("{}", super::html_to_node(html));
+        let nodes = super::html_to_nodes(html);
+        println!("{:?}", nodes);
+        let link = NodeElement {
+            tag: "a".to_owned(),
+            attrs: None,
+            children: Some(nodes!["Text"]),
+        };
+        assert_eq!(
+            nodes,
+            nodes![link, p().text("img:").child(img("https://me"))]
+        );
+        #[allow(deprecated)]
+        let json = super::html_to_node(html);
+        assert_eq!(json, serde_json::to_string(&nodes).unwrap());
+    }
+
+    #[test]
+    fn build_nodes() {
+        let content = nodes![
+            h3().text("Title"),
+            p().text("Go to ")
+                .child(a("https://telegra.ph").text("Telegraph")),
+            figure().image("/file/1.jpg").caption("Caption"),
+            ol().items(["one", "two"]),
+        ];
+        assert_eq!(
+            serde_json::to_string(&content).unwrap(),
+            concat!(
+                r#"[{"tag":"h3","children":["Title"]},"#,
+                r#"{"tag":"p","children":["Go to ",{"tag":"a","attrs":{"href":"https://telegra.ph"},"children":["Telegraph"]}]},"#,
+                r#"{"tag":"figure","children":[{"tag":"img","attrs":{"src":"/file/1.jpg"}},{"tag":"figcaption","children":["Caption"]}]},"#,
+                r#"{"tag":"ol","children":[{"tag":"li","children":["one"]},{"tag":"li","children":["two"]}]}]"#,
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "markdown")]
+    fn markdown_to_nodes() {
+        let markdown = r#"# Title
+
+### Section
+
+Text with **bold**, ~~struck~~ and a [link](https://telegra.ph).
+
+![A cat](https://example.com/cat.jpg)
+
+> quoted
+>
+> twice
+
+- [x] done
+- [ ] todo
+
+1. first
+
+```rust
+fn main() {}
+```
+
+| Name | Value |
+|------|-------|
+| a    | 1     |
+"#;
+        assert_eq!(
+            crate::markdown_to_nodes(markdown).unwrap(),
+            nodes![
+                h3().text("Title"),
+                h4().text("Section"),
+                p().text("Text with ")
+                    .child(strong().text("bold"))
+                    .text(", ")
+                    .child(s().text("struck"))
+                    .text(" and a ")
+                    .child(a("https://telegra.ph").text("link"))
+                    .text("."),
+                figure()
+                    .image("https://example.com/cat.jpg")
+                    .caption("A cat"),
+                blockquote().text("quoted").child(br()).text("twice"),
+                ul().item("☑ done").item("☐ todo"),
+                ol().item("first"),
+                pre().child(code().text("fn main() {}\n")),
+                pre().text("Name | Value\n-----|------\na    | 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_markdown() {
+        let content = nodes![
+            h3().text("Title"),
+            h4().text("Section"),
+            p().text("1. Not a list, *not* emphasis. ")
+                .child(em().text("Em "))
+                .child(a("https://telegra.ph").text("link"))
+                .child(br())
+                .child(code().text("a`b"))
+                .child(u().text("under")),
+            figure().image("/file/1.jpg").caption("A cat"),
+            figure().iframe("/embed/youtube?url=x").caption("Video"),
+            blockquote().text("quoted").child(br()).text("twice"),
+            aside().text("aside"),
+            ul().item("one").item(b().text("two")),
+            ol().item("first")
+                .item(li().text("second").child(ul().item("nested"))),
+            pre().child(code().text("fn main() {}\n")),
+            hr(),
+        ];
+        let markdown = crate::nodes_to_markdown(&content);
+        assert_eq!(
+            markdown,
+            r#"## Title
+
+### Section
+
+1\. Not a list, \*not\* emphasis. *Em* [link](https://telegra.ph)\
+``a`b``<u>under</u>
+
+![A cat](/file/1.jpg)
+
+<figure><iframe src="/embed/youtube?url=x"></iframe><figcaption>Video</figcaption></figure>
+
+> quoted\
+> twice
+
+<aside>aside</aside>
+
+- one
+- **two**
+
+1. first
+2. second
+   - nested
+
+```
+fn main() {}
+```
+
+---
+"#
+        );
+
+        #[cfg(feature = "markdown")]
+        {
+            // Everything but raw HTML converts back into the same nodes.
+            let content = nodes![
+                h3().text("Title"),
+                p().text("1. Not a list, *not* emphasis. ")
+                    .child(em().text("Em"))
+                    .child(br())
+                    .child(a("https://telegra.ph").text("link")),
+                figure().image("/file/1.jpg").caption("A cat"),
+                blockquote().text("quoted").child(br()).text("twice"),
+                ol().item("first")
+                    .item(li().text("second").child(ul().item("nested"))),
+                pre().child(code().text("fn main() {}\n")),
+                hr(),
+            ];
+            let markdown = crate::nodes_to_markdown(&content);
+            assert_eq!(crate::markdown_to_nodes(&markdown).unwrap(), content);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "markdown")]
+    fn markdown_raw_html() {
+        let round_trips = [
+            nodes![aside().text("aside")],
+            nodes![aside().text("with ").child(b().text("bold"))],
+            nodes![p()
+                .text("Some ")
+                .child(u().text("underlined"))
+                .text(" text")],
+            nodes![p().child(strong().text("bold ").child(u().text("and under")))],
+            nodes![figure().iframe("/embed/youtube?url=x").caption("Video")],
+            nodes![figure().iframe("/embed/youtube?url=x")],
+            nodes![figure().video("/file/1.mp4").caption("A clip")],
+            nodes![figure().video("/file/1.mp4")],
+        ];
+        for content in round_trips {
+            let markdown = crate::nodes_to_markdown(&content);
+            #[cfg(any(feature = "kuchiki", feature = "html"))]
+            assert_eq!(
+                crate::markdown_to_nodes(&markdown).unwrap(),
+                content,
+                "{}",
+                markdown
+            );
+            #[cfg(not(any(feature = "kuchiki", feature = "html")))]
+            assert!(
+                matches!(
+                    crate::markdown_to_nodes(&markdown),
+                    Err(crate::Error::UnsupportedHtml(_))
+                ),
+                "{}",
+                markdown
+            );
+        }
+
+        // Comments carry no content and never need a parser.
+        assert_eq!(
+            crate::markdown_to_nodes("<!-- note -->\n\nText <!-- inline -->").unwrap(),
+            nodes![p().text("Text")]
+        );
+    }
+
+    #[test]
+    fn render_html() {
+        let content = nodes![
+            h3().text("Title"),
+            p().text("<b> & \"quotes\"")
+                .child(br())
+                .child(a("/?a=1&b=2").text("link")),
+            figure().image("/file/1.jpg").caption("Caption"),
+            ul().item("one")
+                .item(li().text("two").child(ol().item("nested"))),
+            pre().text("  keep\n    this"),
+        ];
+        assert_eq!(
+            crate::nodes_to_html(&content),
+            concat!(
+                "<h3>Title</h3>",
+                "<p>&lt;b&gt; &amp; &quot;quotes&quot;<br><a href=\"/?a=1&amp;b=2\">link</a></p>",
+                "<figure><img src=\"/file/1.jpg\"><figcaption>Caption</figcaption></figure>",
+                "<ul><li>one</li><li>two<ol><li>nested</li></ol></li></ul>",
+                "<pre>  keep\n    this</pre>",
+            )
+        );
+
+        let pretty = HtmlOptions::new().pretty(true);
+        assert_eq!(
+            crate::nodes_to_html_with(&content, &pretty),
+            r#"<h3>Title</h3>
+<p>&lt;b&gt; &amp; &quot;quotes&quot;<br><a href="/?a=1&amp;b=2">link</a></p>
+<figure>
+  <img src="/file/1.jpg">
+  <figcaption>Caption</figcaption>
+</figure>
+<ul>
+  <li>one</li>
+  <li>
+    two
+    <ol>
+      <li>nested</li>
+    </ol>
+  </li>
+</ul>
+<pre>  keep
+    this</pre>
+"#
+        );
+
+        let document = HtmlOptions::new()
+            .document("A <title>")
+            .author("Author", Some("https://t.me/author"))
+            .date(NaiveDate::from_ymd_opt(2019, 5, 9).unwrap());
+        assert_eq!(
+            crate::nodes_to_html_with(&nodes![p().text("Hello")], &document),
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>A &lt;title&gt;</title>
+</head>
+<body>
+<div class="tl_page">
+<header class="tl_article_header">
+<h1>A &lt;title&gt;</h1>
+<address><a rel="author" href="https://t.me/author">Author</a><time datetime="2019-05-09">May 9, 2019</time></address>
+</header>
+<article class="tl_article_content"><p>Hello</p></article>
+</div>
+</body>
+</html>
+"#
+        );
+    }
+
+    #[test]
+    fn render_text() {
+        let content = nodes![
+            h3().text("Title"),
+            p().text("Read the ")
+                .child(a("https://docs.rs").text("docs"))
+                .text(" at ")
+                .child(a("https://telegra.ph").text("https://telegra.ph"))
+                .child(br())
+                .text("now."),
+            figure().image("/file/1.jpg").caption("Caption"),
+            ul().item("one")
+                .item(li().text("two").child(ol().item("first").item("second"))),
+            blockquote().text("Quote"),
+            hr(),
+            pre().text("fn main() {\n    println!();\n}"),
+        ];
+        assert_eq!(
+            crate::nodes_to_text(&content),
+            "Title\n\nRead the docs at https://telegra.ph\nnow.\n\nCaption\n\n\
+             • one\n• two\n  1. first\n  2. second\n\nQuote\n\n\
+             fn main() {\n    println!();\n}"
+        );
+        let with_links = TextOptions::new().link_targets(true);
+        assert!(crate::nodes_to_text_with(&content, &with_links)
+            .contains("Read the docs (https://docs.rs) at https://telegra.ph\n"));
+
+        assert_eq!(
+            crate::nodes_to_summary(&content, 25),
+            "Title Read the docs at…"
+        );
+        assert_eq!(crate::nodes_to_summary(&content[..1], 5), "Title");
+        assert_eq!(crate::nodes_to_summary(&content[..1], 4), "Tit…");
+    }
+
+    #[test]
+    #[cfg(feature = "kuchiki")]
+    fn sanitize_nodes() {
+        use crate::sanitize::SanitizePolicy;
+
+        let html = r#"<h1 class="title">Title</h1>
+<div><p>One <span style="color: red">red</span> word</p>text<img src="java&#9;script:alert(1)"></div>
+<section>Section with <a href="https://telegra.ph" target="_blank">a link</a>
+and <a href="JavaScript:alert(1)">a script</a></section>
+<h6>Small</h6>
+<script>alert(1)</script><style>p {}</style>
+<table><thead><tr><th>Name</th><th>Value</th></tr></thead>
+<tbody><tr><td>width</td><td><b>10</b></td></tr></tbody></table>"#;
+        let policy = SanitizePolicy::new();
+        let nodes: Vec<_> = crate::sanitize_html(html, &policy)
+            .into_iter()
+            .filter(|node| !matches!(node, Node::Text(text) if text.trim().is_empty()))
+            .collect();
+        assert_eq!(
+            nodes,
+            nodes![
+                h3().text("Title"),
+                p().text("One red word"),
+                p().text("text"),
+                p().text("Section with ")
+                    .child(a("https://telegra.ph").text("a link"))
+                    .text(" and a script"),
+                h4().text("Small"),
+                pre().text("Name  | Value\n------|------\nwidth | 10"),
+            ]
+        );
+        assert!(validate(&nodes).is_empty());
+
+        // Blocks that can't be nested in their parent are unwrapped into it.
+        let div = |text: &str| NodeElement {
+            tag: "div".to_owned(),
+            attrs: None,
+            children: Some(nodes![text]),
+        };
+        let nodes = policy.sanitize(&nodes![
+            a("https://telegra.ph").child(div("link")),
+            p().text("One ").child(div("two")),
+        ]);
+        assert_eq!(
+            nodes,
+            nodes![a("https://telegra.ph").text("link"), p().text("One two")]
+        );
+        assert!(validate(&nodes).is_empty());
+
+        // Tables become lines of text where a `pre` can't go.
+        let element = |tag: &str, children: Vec<Node>| NodeElement {
+            tag: tag.to_owned(),
+            attrs: None,
+            children: Some(children),
+        };
+        let row = |a: &str, b: &str| {
+            element(
+                "tr",
+                nodes![element("td", nodes![a]), element("td", nodes![b])],
+            )
+        };
+        let table = element("table", nodes![row("a", "1"), row("bc", "2")]);
+        let nodes = policy.sanitize(&nodes![
+            p().text("Table: ").child(table.clone()),
+            ul().item(table),
+        ]);
+        assert_eq!(
+            nodes,
+            nodes![
+                p().text("Table: a  | 1").child(br()).text("bc | 2"),
+                ul().item(pre().text("a  | 1\nbc | 2")),
+            ]
+        );
+        assert!(validate(&nodes).is_empty());
+
+        let policy = SanitizePolicy::new()
+            .drop_tag("h6")
+            .rename("span", "b")
+            .tables_to_pre(false)
+            .allow_scheme("data");
+        let html = r#"<h6>Small</h6><p><span>bold</span><img src="data:image/png;base64,AA=="></p><table><tr><td>cell</td></tr></table>"#;
+        assert_eq!(
+            crate::sanitize_html(html, &policy),
+            nodes![
+                p().child(b().text("bold"))
+                    .child(img("data:image/png;base64,AA==")),
+                "cell"
+            ]
+        );
+        assert!(policy.is_url_allowed("/file/1.jpg"));
+        assert!(!policy.is_url_allowed(" vbscript:msgbox"));
+    }
+
+    #[test]
+    #[cfg(feature = "kuchiki")]
+    fn convert_html_report() {
+        use crate::convert::ConvertOptions;
+        use kuchikiki::traits::TendrilSink;
+
+        let html = r#"<!DOCTYPE html><p>One<!-- note --></p><p><span>Two</span>
+<a href="javascript:void(0)" title="">link</a><img src=""></p><script>x()</script>"#;
+        let conversion = crate::convert_html(html, &ConvertOptions::new()).unwrap();
+        assert_eq!(
+            conversion.nodes,
+            nodes![p().text("One"), p().text("Two link")]
+        );
+        assert!(!conversion.is_lossless());
+        let dropped: Vec<_> = conversion.dropped.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            dropped,
+            [
+                r#"removed doctype "html""#,
+                r#"removed comment " note ""#,
+                "unwrapped <span>",
+                r#"removed href="javascript:void(0)" from <a>"#,
+                "removed title from <a>",
+                "unwrapped <a>",
+                "removed src from <img>",
+                "removed <img> with its content",
+                "removed <script> with its content",
+            ]
+        );
+
+        let conversion = crate::convert_html("<h3>Title</h3>", &ConvertOptions::new()).unwrap();
+        assert!(conversion.is_lossless());
+
+        let document = kuchikiki::parse_html().one("<p>One</p><!-- note --><p>Two</p>");
+        let body = document.last_child().unwrap().last_child().unwrap();
+        assert_eq!(
+            crate::doms_to_node_list(body.children()),
+            nodes![p().text("One"), p().text("Two")]
+        );
+        #[allow(deprecated)]
+        let nodes = crate::doms_to_nodes(body.children());
+        assert_eq!(nodes, None);
+    }
+
+    #[test]
+    #[cfg(any(feature = "kuchiki", feature = "html"))]
+    fn normalize_whitespace() {
+        use crate::convert::ConvertOptions;
+
+        let html = "
+<h3>  Title  </h3>
+<p>
+    Some   <b>bold </b> text
+    with an <img src=\"/file/1.jpg\"> image<br>
+    and a break.
+</p>
+<ul>
+    <li>one</li>
+    <li> two </li>
+</ul>
+<pre>  fn main() {
+      println!();
+  }
+</pre>
+";
+        let expected = nodes![
+            h3().text("Title"),
+            p().text("Some ")
+                .child(b().text("bold "))
+                .text("text with an ")
+                .child(img("/file/1.jpg"))
+                .text(" image")
+                .child(br())
+                .text("and a break."),
+            ul().item("one").item("two"),
+            pre().text("  fn main() {\n      println!();\n  }\n"),
+        ];
+        assert_eq!(super::html_to_nodes(html), expected);
+        let conversion = crate::convert_html(html, &ConvertOptions::new()).unwrap();
+        assert_eq!(conversion.nodes, expected);
+
+        let options = ConvertOptions::new().newlines_to_br(true);
+        let conversion = crate::convert_html("<p>Line one\r\n  line two</p>", &options).unwrap();
+        assert_eq!(
+            conversion.nodes,
+            nodes![p().text("Line one").child(br()).text("line two")]
+        );
     }
 
     #[tokio::test]
     async fn create_and_revoke_account() {
-        let result = Telegraph::create_account("sample", "a", None).await;
+        let server = FakeTelegraph::start().await.unwrap();
+        let result = Telegraph::create_account(&server.account("sample").author_name("a")).await;
         println!("{:?}", result);
         assert!(result.is_ok());
 
-        let mut telegraph = Telegraph::new("test")
-            .access_token(&result.unwrap().access_token.unwrap().to_owned())
+        let mut telegraph = server
+            .account("test")
+            .access_token(&result.unwrap().access_token.unwrap())
             .create()
             .await
             .unwrap();
         let result = telegraph.revoke_access_token().await;
         println!("{:?}", result);
         assert!(result.is_ok());
+        assert!(telegraph.get_page_list(0, 3).await.is_ok());
     }
 
     #[tokio::test]
     async fn edit_account_info() {
-        let result = Telegraph::new("test")
-            .access_token("d3b25feccb89e508a9114afb82aa421fe2a9712b963b387cc5ad71e58722")
+        let server = FakeTelegraph::start().await.unwrap();
+        let result = server
+            .account("test")
             .create()
             .await
             .unwrap()
@@ -559,35 +2065,68 @@ mod tests {
 
     #[tokio::test]
     async fn get_account_info() {
-        let result = Telegraph::new("test")
-            .access_token("d3b25feccb89e508a9114afb82aa421fe2a9712b963b387cc5ad71e58722")
+        let server = FakeTelegraph::start().await.unwrap();
+        let result = server
+            .account("test")
             .create()
             .await
             .unwrap()
-            .get_account_info(&["short_name"])
+            .get_account_info(&[AccountField::ShortName])
             .await;
         println!("{:?}", result);
-        assert!(result.is_ok());
+        let info = result.unwrap();
+        assert_eq!(info.short_name().unwrap(), "test");
+        assert!(matches!(
+            info.page_count(),
+            Err(Error::FieldNotRequested(AccountField::PageCount))
+        ));
     }
 
     #[tokio::test]
-    async fn create_get_edit_page() {
-        let telegraph = Telegraph::new("test")
-            .access_token("d3b25feccb89e508a9114afb82aa421fe2a9712b963b387cc5ad71e58722")
+    async fn account_info_fields() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server
+            .account("test")
+            .author_name("Author")
             .create()
             .await
             .unwrap();
+        telegraph
+            .create_page("title", &nodes!["text"], false)
+            .await
+            .unwrap();
+
+        let info = telegraph.account_info().send().await.unwrap();
+        assert_eq!(info.fields(), AccountField::DEFAULT);
+        assert_eq!(info.author_name().unwrap(), "Author");
+
+        let info = telegraph
+            .account_info()
+            .field(AccountField::PageCount)
+            .fields([AccountField::AuthUrl, AccountField::PageCount])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            info.fields(),
+            [AccountField::PageCount, AccountField::AuthUrl]
+        );
+        assert_eq!(info.page_count().unwrap(), 1);
+        assert!(info.auth_url().is_ok());
+        assert!(info.short_name().is_err());
+    }
+
+    #[tokio::test]
+    async fn create_get_edit_page() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
         let page = telegraph
-            .create_page(
-                "OVO",
-                r#"[{"tag":"p","children":["Hello,+world!"]}]"#,
-                false,
-            )
+            .create_page("OVO", &nodes![p().text("Hello, world!")], false)
             .await;
         println!("{:?}", page);
         assert!(page.is_ok());
 
-        let page = Telegraph::get_page(&page.unwrap().path, true).await;
+        let page = telegraph.page(&page.unwrap().path, true).await;
         println!("{:?}", page);
         assert!(page.is_ok());
 
@@ -595,39 +2134,1188 @@ mod tests {
             .edit_page(
                 &page.unwrap().path,
                 "QAQ",
-                r#"[{"tag":"p","children":["Goodbye,+world!"]}]"#,
+                &nodes![p().text("Goodbye, world!")],
                 false,
             )
             .await;
         println!("{:?}", page);
-        assert!(page.is_ok());
+        assert_eq!(page.unwrap().title, "QAQ");
+    }
+
+    #[test]
+    fn validate_nodes() {
+        let mut bad_attrs = NodeElement {
+            tag: "p".to_owned(),
+            attrs: Some([("class".to_owned(), Some("x".to_owned()))].into()),
+            children: None,
+        };
+        let violations = validate(&nodes![
+            h3().text("Title"),
+            p().child(a("https://telegra.ph").text("link")).child(br()),
+            figure().image("/file/1.jpg").caption("Caption"),
+            ul().item(ol().item("nested")),
+        ]);
+        assert!(violations.is_empty(), "{:?}", violations);
+
+        let content = nodes![
+            NodeElement {
+                tag: "div".to_owned(),
+                attrs: None,
+                children: None
+            },
+            bad_attrs.clone(),
+            p().child(blockquote().text("quote")),
+            a(" ").text("empty"),
+            li().text("orphan"),
+            NodeElement {
+                tag: "ul".to_owned(),
+                attrs: None,
+                children: Some(nodes!["text"])
+            },
+            img("/file/1.jpg").text("child"),
+        ];
+        let kinds: Vec<_> = validate(&content)
+            .into_iter()
+            .map(|violation| (violation.path, violation.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (vec![0], ViolationKind::DisallowedTag("div".to_owned())),
+                (
+                    vec![1],
+                    ViolationKind::DisallowedAttribute {
+                        tag: "p".to_owned(),
+                        attribute: "class".to_owned()
+                    }
+                ),
+                (
+                    vec![2, 0],
+                    ViolationKind::IllegalNesting {
+                        parent: Some("p".to_owned()),
+                        child: "<blockquote>".to_owned()
+                    }
+                ),
+                (
+                    vec![3],
+                    ViolationKind::EmptyAttribute {
+                        tag: "a".to_owned(),
+                        attribute: "href".to_owned()
+                    }
+                ),
+                (
+                    vec![4],
+                    ViolationKind::IllegalNesting {
+                        parent: None,
+                        child: "<li>".to_owned()
+                    }
+                ),
+                (
+                    vec![5, 0],
+                    ViolationKind::IllegalNesting {
+                        parent: Some("ul".to_owned()),
+                        child: "text".to_owned()
+                    }
+                ),
+                (
+                    vec![6],
+                    ViolationKind::IllegalNesting {
+                        parent: Some("img".to_owned()),
+                        child: "children".to_owned()
+                    }
+                ),
+            ]
+        );
+
+        bad_attrs.attrs = None;
+        bad_attrs.children = Some(vec![Node::Text("x".repeat(MAX_CONTENT_SIZE))]);
+        let violations = validate(&[Node::NodeElement(bad_attrs)]);
+        assert!(matches!(
+            violations[..],
+            [Violation {
+                kind: ViolationKind::ContentTooBig { .. },
+                ..
+            }]
+        ));
     }
 
     #[tokio::test]
-    async fn get_page_list() {
-        let telegraph = Telegraph::new("test")
-            .access_token("d3b25feccb89e508a9114afb82aa421fe2a9712b963b387cc5ad71e58722")
+    async fn validate_before_sending() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let content = nodes![p().child(h3().text("Title"))];
+
+        let telegraph = server.account("test").create().await.unwrap();
+        assert!(telegraph
+            .create_page("title", &content, false)
+            .await
+            .is_ok());
+
+        let telegraph = server
+            .account("test")
+            .validate_content(true)
             .create()
             .await
             .unwrap();
+        let requests = server.requests().len();
+        let err = telegraph
+            .create_page("title", &content, false)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, Error::InvalidContent(violations) if violations.len() == 1));
+        assert_eq!(
+            err.to_string(),
+            "invalid content: <p> cannot contain <h3> (node 0/0)"
+        );
+        assert_eq!(server.requests().len(), requests);
+    }
+
+    #[tokio::test]
+    async fn long_page() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let paragraph = "All work and no play makes Jack a dull boy. ".repeat(20);
+        let content: Vec<_> = (0..200).map(|_| p().text(&*paragraph).build()).collect();
+
+        let requests = server.requests().len();
+        let pages = telegraph
+            .create_long_page("Report", &content)
+            .await
+            .unwrap();
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[1].title, "Report (Part 2/3)");
+        assert_eq!(
+            server.requests()[requests..],
+            [
+                "createPage",
+                "createPage",
+                "createPage",
+                "editPage",
+                "editPage"
+            ]
+        );
+        let links = [
+            p().child(a(&pages[1].url).text("Part 2 →")),
+            p().child(a(&pages[0].url).text("← Part 1"))
+                .text(" | ")
+                .child(a(&pages[2].url).text("Part 3 →")),
+            p().child(a(&pages[1].url).text("← Part 2")),
+        ];
+        let mut joined = vec![];
+        for (page, links) in pages.iter().zip(links) {
+            let mut part = telegraph
+                .page(&page.path, true)
+                .await
+                .unwrap()
+                .content
+                .unwrap();
+            assert!(serde_json::to_string(&part).unwrap().len() <= MAX_CONTENT_SIZE);
+            assert_eq!(part.pop(), Some(links.build()));
+            assert_eq!(part.pop(), Some(hr().build()));
+            joined.extend(part);
+        }
+        assert_eq!(joined, content);
+
+        let paths: Vec<_> = pages.iter().map(|page| page.path.as_str()).collect();
+        let edited = telegraph
+            .edit_long_page(&paths, "Report", &content[..100])
+            .await
+            .unwrap();
+        assert_eq!(edited.len(), 2);
+        assert_eq!(edited[0].path, pages[0].path);
+        let stub = telegraph.page(&pages[2].path, true).await.unwrap();
+        assert_eq!(stub.title, "Report");
+        assert_eq!(stub.content.unwrap().len(), 1);
+
+        let pages = telegraph
+            .create_long_page("Short", &content[..1])
+            .await
+            .unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].title, "Short");
+    }
+
+    #[test]
+    fn split_big_blocks() {
+        let text = "word ".repeat(1000);
+        let parts = split::split_content(&nodes![p().text(&*text)], 1000);
+        assert!(parts.len() > 5);
+        let mut joined = String::new();
+        for part in &parts {
+            assert!(serde_json::to_string(part).unwrap().len() <= 1000);
+            match &part[..] {
+                [Node::NodeElement(NodeElement {
+                    tag,
+                    children: Some(children),
+                    ..
+                })] if tag == "p" => match &children[..] {
+                    [Node::Text(text)] => joined.push_str(text),
+                    _ => panic!("{:?}", children),
+                },
+                _ => panic!("{:?}", part),
+            }
+        }
+        assert_eq!(joined, text);
+    }
+
+    #[tokio::test]
+    async fn get_page_list() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        for title in &["1", "2", "3", "4"] {
+            telegraph
+                .create_page(title, &nodes!["text"], false)
+                .await
+                .unwrap();
+        }
         let page_list = telegraph.get_page_list(0, 3).await;
         println!("{:?}", page_list);
-        assert!(page_list.is_ok());
+        let page_list = page_list.unwrap();
+        assert_eq!(page_list.total_count, 4);
+        assert_eq!(page_list.pages.len(), 3);
+        assert_eq!(page_list.pages[0].title, "4");
+    }
+
+    #[tokio::test]
+    async fn stream_pages() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        for title in &["1", "2", "3", "4", "5"] {
+            telegraph
+                .create_page(title, &nodes!["text"], false)
+                .await
+                .unwrap();
+        }
+
+        let pages: Vec<_> = telegraph.page_stream(2).try_collect().await.unwrap();
+        let titles: Vec<_> = pages.iter().map(|page| page.title.as_str()).collect();
+        assert_eq!(titles, ["5", "4", "3", "2", "1"]);
+        let requests = server.requests();
+        assert_eq!(requests.iter().filter(|m| *m == "getPageList").count(), 3);
+
+        let pages: Vec<_> = telegraph.pages_with_content(2).try_collect().await.unwrap();
+        assert_eq!(pages.len(), 5);
+        assert!(pages.iter().all(|page| page.content.is_some()));
     }
 
     #[tokio::test]
     async fn get_views() {
-        let views = Telegraph::get_views("Sample-Page-12-15", &vec![2016, 12]).await;
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let page = telegraph
+            .create_page("Sample Page", &nodes!["text"], false)
+            .await
+            .unwrap();
+        server.add_views(&page.path, 3);
+        server.add_views_at(&page.path, (2016, 12, 24, 10), 2);
+        server.add_views_at(&page.path, (2017, 1, 1, 0), 5);
+        let views = telegraph
+            .views(&page.path, ViewsQuery::month(2016, 12))
+            .await;
         println!("{:?}", views);
-        assert!(views.is_ok());
+        assert_eq!(views.unwrap().views, 2);
+        let views = telegraph.views(&page.path, ViewsQuery::Total).await;
+        assert_eq!(views.unwrap().views, 10);
+
+        let invalid = telegraph
+            .views(&page.path, ViewsQuery::day(2019, 2, 29))
+            .await;
+        assert!(matches!(invalid, Err(Error::InvalidViewsQuery(_))));
+        assert!(ViewsQuery::hour(2019, 2, 28, 25).validate().is_err());
+        assert!(ViewsQuery::year(1999).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn views_series() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let page = telegraph
+            .create_page("Sample Page", &nodes!["text"], false)
+            .await
+            .unwrap();
+        server.add_views_at(&page.path, (2020, 2, 28, 23), 4);
+        server.add_views_at(&page.path, (2020, 3, 1, 8), 1);
+        let date = |m, d| NaiveDate::from_ymd_opt(2020, m, d).unwrap();
+
+        let series = telegraph
+            .views_series(&page.path, Granularity::Day, date(2, 28), date(3, 1))
+            .await
+            .unwrap();
+        assert_eq!(
+            series.to_csv(),
+            "period,views\n2020-02-28,4\n2020-02-29,0\n2020-03-01,1\n"
+        );
+
+        let series = telegraph
+            .views_series(&page.path, Granularity::Month, date(1, 31), date(3, 1))
+            .await
+            .unwrap();
+        let views: Vec<_> = series.points.iter().map(|point| point.views).collect();
+        assert_eq!(views, [0, 4, 1]);
+
+        let series = telegraph
+            .views_series(&page.path, Granularity::Hour, date(2, 28), date(2, 28))
+            .await
+            .unwrap();
+        assert_eq!(series.points.len(), 24);
+        assert_eq!(series.points[23].period, ViewsQuery::hour(2020, 2, 28, 23));
+        assert_eq!(series.total(), 4);
+
+        let reversed = telegraph
+            .views_series(&page.path, Granularity::Day, date(3, 1), date(2, 28))
+            .await;
+        assert!(reversed.is_err());
+    }
+
+    #[tokio::test]
+    async fn injected_faults() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+
+        server.inject_for("getPageList", Fault::FloodWait(5));
+        server.inject(Fault::Status(502));
+        let result = telegraph.get_page_list(0, 1).await;
+        let error = result.unwrap_err();
+        assert_eq!(error.api_kind(), Some(&ApiErrorKind::FloodWait(5)));
+        assert!(error.is_retryable());
+        let result = telegraph.get_page_list(0, 1).await;
+        assert!(matches!(result, Err(Error::ReqwestError(_))));
+        assert!(result.unwrap_err().is_retryable());
+        assert!(telegraph.get_page_list(0, 1).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn api_error_context() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+
+        let error = telegraph.page("Missing-Page", false).await.unwrap_err();
+        println!("{}", error);
+        assert!(matches!(
+            &error,
+            Error::ApiError { kind: ApiErrorKind::PageNotFound, method: "getPage", path: Some(path) }
+                if path == "Missing-Page"
+        ));
+        assert!(!error.is_retryable());
+        assert_eq!(
+            ApiErrorKind::parse("SOMETHING_NEW"),
+            ApiErrorKind::Unknown("SOMETHING_NEW".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let retries = Arc::new(AtomicU32::new(0));
+        let counter = retries.clone();
+        let policy = RetryPolicy::new()
+            .max_attempts(3)
+            .base_delay(Duration::from_millis(10))
+            .on_retry(move |event| {
+                println!("{:?}", event);
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        let telegraph = server.account("test").retry(policy).create().await.unwrap();
+
+        server.inject_for("getPageList", Fault::Status(503));
+        server.inject_for("getPageList", Fault::FloodWait(1));
+        assert!(telegraph.get_page_list(0, 1).await.is_ok());
+        assert_eq!(retries.load(Ordering::SeqCst), 2);
+
+        // The page may have been created despite the error.
+        server.inject_for("createPage", Fault::Status(500));
+        assert!(telegraph
+            .create_page("title", &nodes!["text"], false)
+            .await
+            .is_err());
+        assert_eq!(retries.load(Ordering::SeqCst), 2);
+        server.inject_for("createPage", Fault::FloodWait(1));
+        assert!(telegraph
+            .create_page("title", &nodes!["text"], false)
+            .await
+            .is_ok());
+        assert_eq!(retries.load(Ordering::SeqCst), 3);
+
+        server.clear_faults();
+        assert!(telegraph.page("Missing-Page", false).await.is_err());
+        assert_eq!(retries.load(Ordering::SeqCst), 3);
+
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(10))
+            .retry_non_idempotent(true);
+        let telegraph = server.account("test").retry(policy).create().await.unwrap();
+        for _ in 0..3 {
+            server.inject_for("createPage", Fault::Status(500));
+        }
+        assert!(telegraph
+            .create_page("title", &nodes!["text"], false)
+            .await
+            .is_err());
+        server.inject_for("createPage", Fault::Status(500));
+        assert!(telegraph
+            .create_page("title", &nodes!["text"], false)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn retry_delay() {
+        let policy = RetryPolicy::new()
+            .max_attempts(4)
+            .base_delay(Duration::from_secs(1))
+            .max_delay(Duration::from_secs(3))
+            .jitter(false)
+            .max_flood_wait(Some(Duration::from_secs(10)));
+        let error = |e: &str| Error::api(e.to_owned(), "createPage", None);
+
+        let transient = error("PAGE_SAVE_FAILED");
+        assert_eq!(policy.delay(1, &transient), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, &transient), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(3, &transient), Some(Duration::from_secs(3)));
+        assert_eq!(policy.delay(4, &transient), None);
+
+        assert_eq!(
+            policy.delay(1, &error("FLOOD_WAIT_7")),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(policy.delay(1, &error("FLOOD_WAIT_70")), None);
+        assert_eq!(policy.delay(1, &error("CONTENT_TOO_BIG")), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_budgets() {
+        let limiter = RateLimiter::new(
+            RateLimit::new()
+                .writes(Quota::per_second(2))
+                .uploads(Quota::per_minute(1)),
+        );
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.clone().acquire(RequestKind::Write).await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        for _ in 0..100 {
+            limiter.acquire(RequestKind::Read).await;
+        }
+        limiter.acquire(RequestKind::Upload).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        limiter.acquire(RequestKind::Upload).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(61));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_shared_by_clones() {
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server
+            .account("test")
+            .rate_limit(RateLimit::new().reads(Quota::per_second(50).burst(1)))
+            .create()
+            .await
+            .unwrap();
+
+        let start = std::time::Instant::now();
+        let tasks: Vec<_> = (0..6)
+            .map(|_| {
+                let telegraph = telegraph.clone();
+                tokio::spawn(async move { telegraph.get_page_list(0, 1).await })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap().is_ok());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
     }
 
-    #[ignore]
     #[tokio::test]
     #[cfg(feature = "upload")]
     async fn upload() {
-        let images = Telegraph::upload(&vec!["1.jpeg", "2.jpeg"]).await;
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let dir = std::env::temp_dir().join("telegraph-rs-upload");
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [dir.join("1.jpeg"), dir.join("2.png")];
+        for file in &files {
+            std::fs::write(file, b"not really an image").unwrap();
+        }
+
+        let images = telegraph.upload_media(&files).await;
         println!("{:?}", images);
-        assert!(images.is_ok());
+        assert_eq!(images.unwrap().len(), 2);
+        assert_eq!(server.uploads()[1].mime.as_deref(), Some("image/png"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "upload")]
+    async fn upload_bytes_and_readers() {
+        use crate::{UploadBytes, UploadReader};
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let files = [
+            UploadBytes::new(b"GIF89a".to_vec(), "1.gif", "image/gif"),
+            UploadBytes::new(bytes::Bytes::from_static(b"png"), "2.png", "image/png"),
+        ];
+        assert_eq!(telegraph.upload_media(&files).await.unwrap().len(), 2);
+
+        let reader = std::io::Cursor::new(b"mp4".to_vec());
+        let files = [UploadReader::new(reader, "3.mp4", "video/mp4").length(3)];
+        let videos = telegraph.upload_media(&files).await.unwrap();
+        let uploads = server.uploads();
+        assert_eq!(uploads[2].src, videos[0].src);
+        assert_eq!(&uploads[0].bytes[..], b"GIF89a");
+        assert_eq!(&uploads[2].bytes[..], b"mp4");
+        assert_eq!(uploads[2].file_name.as_deref(), Some("3.mp4"));
+        assert_eq!(uploads[2].mime.as_deref(), Some("video/mp4"));
+        assert!(matches!(
+            telegraph.upload_media(&files).await,
+            Err(Error::ReaderConsumed)
+        ));
+
+        let file = UploadBytes::from(bytes::Bytes::from_static(b"\xFF\xD8\xFF\xE0"));
+        assert_eq!((file.file_name(), file.mime()), ("file", "image/jpeg"));
+        let file = UploadBytes::from(bytes::Bytes::from_static(b"<svg></svg>"));
+        assert_eq!(file.mime(), "application/octet-stream");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "upload")]
+    async fn upload_progress() {
+        use crate::{UploadBytes, UploadEvent, UploadReader};
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let sink = events.clone();
+        let policy = RetryPolicy::new()
+            .max_attempts(2)
+            .base_delay(Duration::from_millis(10));
+        let telegraph = server
+            .account("test")
+            .retry(policy)
+            .on_upload_progress(move |event| sink.lock().unwrap().push(event.clone()))
+            .create()
+            .await
+            .unwrap();
+        let take_events = || std::mem::take(&mut *events.lock().unwrap());
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.resize(150_000, 0);
+        let files = [UploadBytes::new(gif, "a.gif", "image/gif")];
+        telegraph.upload_media(&files).await.unwrap();
+        let events = take_events();
+        let request = match events[0] {
+            UploadEvent::Started {
+                request,
+                files: 1,
+                total_size: Some(150_000),
+            } => request,
+            ref event => panic!("unexpected {:?}", event),
+        };
+        let sent: Vec<_> = events[1..events.len() - 1]
+            .iter()
+            .map(|event| match event {
+                UploadEvent::Sent(progress) => {
+                    assert_eq!(progress.request, request);
+                    assert_eq!(progress.file_name, "a.gif");
+                    assert_eq!(progress.size, Some(150_000));
+                    assert_eq!(progress.sent, progress.total_sent);
+                    progress.total_sent
+                }
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        assert_eq!(sent, [65_536, 131_072, 150_000]);
+        assert_eq!(
+            events.last(),
+            Some(&UploadEvent::Finished {
+                request,
+                total_sent: 150_000,
+                ok: true
+            })
+        );
+
+        // A retried request starts over, with the same number.
+        server.inject_for("upload", Fault::Status(500));
+        telegraph.upload_media(&files).await.unwrap();
+        let events = take_events();
+        let started: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                UploadEvent::Started { request, .. } => Some(*request),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(started.len(), 2);
+        assert_eq!(started[0], started[1]);
+        assert_ne!(started[0], request);
+        assert!(matches!(
+            events.last(),
+            Some(UploadEvent::Finished {
+                total_sent: 150_000,
+                ok: true,
+                ..
+            })
+        ));
+
+        let reader = std::io::Cursor::new(b"mp4".to_vec());
+        let files = [UploadReader::new(reader, "b.mp4", "video/mp4")];
+        telegraph.upload_media(&files).await.unwrap();
+        let events = take_events();
+        assert!(matches!(
+            events[0],
+            UploadEvent::Started {
+                files: 1,
+                total_size: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &events[1],
+            UploadEvent::Sent(progress) if progress.sent == 3 && progress.size.is_none()
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "upload-cache")]
+    async fn upload_cache() {
+        use crate::{
+            cache::{CacheKey, JsonFileCache, MemoryCache, UploadCache},
+            UploadBytes, UploadReader,
+        };
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let cache = Arc::new(MemoryCache::new());
+        let telegraph = server
+            .account("test")
+            .upload_cache(cache.clone())
+            .create()
+            .await
+            .unwrap();
+        let gif = |i: u8| {
+            UploadBytes::new(
+                vec![b'G', b'I', b'F', b'8', b'9', b'a', i],
+                "a.gif",
+                "image/gif",
+            )
+        };
+
+        let first = telegraph.upload_media(&[gif(0), gif(1)]).await.unwrap();
+        let second = telegraph
+            .upload_media(&[gif(2), gif(1), gif(0)])
+            .await
+            .unwrap();
+        assert_eq!(server.uploads().len(), 3);
+        assert_eq!(second[1].src, first[1].src);
+        assert_eq!(second[2].src, first[0].src);
+        assert_eq!(cache.len(), 3);
+
+        let key = CacheKey::of(gif(0).bytes());
+        assert_eq!(key.to_string().parse::<CacheKey>().unwrap(), key);
+        assert!(cache.remove(&key).unwrap());
+        assert_eq!(cache.remove_src(&first[1].src).unwrap(), 1);
+        telegraph
+            .upload_media(&[gif(0), gif(1), gif(2)])
+            .await
+            .unwrap();
+        assert_eq!(server.uploads().len(), 5);
+        // Readers aren't hashed, so they are always uploaded and never cached.
+        let reader = std::io::Cursor::new(b"GIF89a\0".to_vec());
+        let files = [UploadReader::new(reader, "a.gif", "image/gif")];
+        telegraph.upload_media(&files).await.unwrap();
+        assert_eq!((server.uploads().len(), cache.len()), (6, 3));
+
+        let path =
+            std::env::temp_dir().join(format!("telegraph-cache-{}.json", std::process::id()));
+        let json = JsonFileCache::open(&path).unwrap();
+        json.insert(&key, "/file/a.gif").unwrap();
+        json.insert(&CacheKey::of(b"b"), "/file/b.gif").unwrap();
+        let json = JsonFileCache::open(&path).unwrap();
+        assert_eq!(json.get(&key).unwrap().as_deref(), Some("/file/a.gif"));
+        assert_eq!(json.remove_src("/file/b.gif").unwrap(), 1);
+        json.clear().unwrap();
+        assert_eq!(JsonFileCache::open(&path).unwrap().get(&key).unwrap(), None);
+        // The files of an upload are written to the cache together.
+        let json = Arc::new(JsonFileCache::open(&path).unwrap());
+        let telegraph = server
+            .account("test")
+            .upload_cache(json.clone())
+            .create()
+            .await
+            .unwrap();
+        let uploaded = telegraph.upload_media(&[gif(3), gif(4)]).await.unwrap();
+        let json = JsonFileCache::open(&path).unwrap();
+        for (i, image) in (3..).zip(&uploaded) {
+            let key = CacheKey::of(gif(i).bytes());
+            assert_eq!(json.get(&key).unwrap(), Some(image.src.clone()));
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        #[cfg(feature = "sqlite-cache")]
+        {
+            use crate::cache::SqliteCache;
+
+            let connection = rusqlite::Connection::open_in_memory().unwrap();
+            let sqlite = SqliteCache::with_connection(connection).unwrap();
+            sqlite.insert(&key, "/file/a.gif").unwrap();
+            sqlite.insert(&key, "/file/b.gif").unwrap();
+            assert_eq!(sqlite.get(&key).unwrap().as_deref(), Some("/file/b.gif"));
+            assert_eq!(sqlite.remove_src("/file/b.gif").unwrap(), 1);
+            assert!(!sqlite.remove(&key).unwrap());
+            let entries = [
+                (key, "/file/a.gif".to_owned()),
+                (CacheKey::of(b"b"), "/file/b.gif".to_owned()),
+            ];
+            sqlite.insert_all(&entries).unwrap();
+            assert_eq!(sqlite.get(&key).unwrap().as_deref(), Some("/file/a.gif"));
+            assert_eq!(sqlite.remove_src("/file/b.gif").unwrap(), 1);
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "upload")]
+    async fn upload_files() {
+        use crate::{upload::sniff_mime, UploadBytes, UploadOptions};
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let gif =
+            |i: u8| UploadBytes::new([b"GIF89a".as_slice(), &[i]].concat(), "a.gif", "image/gif");
+        let files = [
+            gif(0),
+            UploadBytes::new(b"<svg></svg>".to_vec(), "b.png", "image/png"),
+            gif(1),
+            UploadBytes::new(b"\xFF\xD8\xFF too big!".to_vec(), "c.jpg", "image/jpeg"),
+            gif(2),
+            // Accepted by the checks but not by the server.
+            UploadBytes::new(b"GIF89a".to_vec(), "d.svg", "image/svg+xml"),
+            gif(3),
+        ];
+        server.inject_for("upload", Fault::Status(500));
+        let options = UploadOptions::new()
+            .max_size(11)
+            .chunk_size(2)
+            .concurrency(2);
+        let results = telegraph.upload_files(&files, &options).await;
+        assert_eq!(results.len(), files.len());
+        assert!(matches!(results[1], Err(Error::UnsupportedFileType)));
+        assert!(matches!(
+            results[3],
+            Err(Error::FileTooBig {
+                size: 12,
+                max_size: 11
+            })
+        ));
+        assert!(results[5].as_ref().unwrap_err().api_kind().is_some());
+        let uploads = server.uploads();
+        assert_eq!(uploads.len(), 4);
+        for i in [0, 2, 4, 6] {
+            let src = &results[i].as_ref().unwrap().src;
+            let upload = uploads.iter().find(|upload| &upload.src == src).unwrap();
+            assert_eq!(upload.bytes, files[i].bytes());
+        }
+
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1A\n"), Some("image/png"));
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypmp42"), Some("video/mp4"));
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypisom"), Some("video/mp4"));
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypheic"), None);
+        assert_eq!(sniff_mime(b"\0\0\0\x14ftypqt  "), None);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "image-processing")]
+    async fn transcode_images() {
+        use crate::{
+            transcode::{fit_image, ImageOptions},
+            UploadBytes, UploadOptions, Uploadable,
+        };
+        use image::{
+            codecs::gif::{GifDecoder, GifEncoder, Repeat},
+            metadata::LoopCount,
+            AnimationDecoder, DynamicImage, Frame, ImageFormat, RgbImage, RgbaImage,
+        };
+        use std::io::Cursor;
+
+        let encode = |image: DynamicImage, format| {
+            let mut bytes = vec![];
+            image
+                .write_to(&mut Cursor::new(&mut bytes), format)
+                .unwrap();
+            bytes
+        };
+        // Noise doesn't compress well.
+        let noise = RgbImage::from_fn(512, 512, |_, _| {
+            image::Rgb([fastrand::u8(..), fastrand::u8(..), fastrand::u8(..)])
+        });
+        let png = encode(DynamicImage::ImageRgb8(noise), ImageFormat::Png);
+        let webp = encode(
+            DynamicImage::ImageRgba8(RgbaImage::new(8, 8)),
+            ImageFormat::WebP,
+        );
+
+        let file = fit_image(
+            &UploadBytes::new(webp, "a.webp", "image/webp"),
+            &ImageOptions::new(),
+        )
+        .unwrap();
+        assert_eq!((file.file_name(), file.mime()), ("a.png", "image/png"));
+        let file = UploadBytes::new(png.clone(), "noise.png", "image/png");
+        assert_eq!(
+            fit_image(&file, &ImageOptions::new()).unwrap().bytes(),
+            &png
+        );
+        let options = ImageOptions::new().max_size(64 * 1024).max_dimension(400);
+        let fitted = fit_image(&file, &options).unwrap();
+        assert_eq!(
+            (fitted.file_name(), fitted.mime()),
+            ("noise.jpg", "image/jpeg")
+        );
+        assert!(fitted.bytes().len() <= 64 * 1024);
+        let fitted = image::load_from_memory(fitted.bytes()).unwrap();
+        assert!(fitted.width() <= 400 && fitted.height() <= 400);
+        assert!(matches!(
+            fit_image(&file, &ImageOptions::new().max_size(100)),
+            Err(Error::FileTooBig { max_size: 100, .. })
+        ));
+        let bmp = UploadBytes::new(
+            encode(DynamicImage::new_rgb8(64, 64), ImageFormat::Bmp),
+            "a.bmp",
+            "image/bmp",
+        );
+        assert!(matches!(
+            fit_image(&bmp, &ImageOptions::new().max_input_size(100)),
+            Err(Error::FileTooBig { max_size: 100, .. })
+        ));
+        let heic = UploadBytes::new(b"\0\0\0\x18ftypheic".to_vec(), "a.heic", "image/heic");
+        assert!(matches!(
+            fit_image(&heic, &options),
+            Err(Error::UnsupportedFileType)
+        ));
+
+        let mut gif = vec![];
+        let frames = (0..3).map(|i| {
+            Frame::new(RgbaImage::from_pixel(
+                256,
+                256,
+                image::Rgba([i * 80, 0, 0, 255]),
+            ))
+        });
+        GifEncoder::new(&mut gif).encode_frames(frames).unwrap();
+        let options = ImageOptions::new().max_dimension(64);
+        let fitted = fit_image(&UploadBytes::new(gif, "a.gif", "image/gif"), &options).unwrap();
+        let frames = GifDecoder::new(Cursor::new(fitted.bytes()))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].buffer().dimensions(), (64, 64));
+        // Played once like the original, which has no loop extension.
+        assert!(!fitted.bytes().windows(11).any(|w| w == b"NETSCAPE2.0"));
+        let mut gif = vec![];
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            encoder.set_repeat(Repeat::Finite(3)).unwrap();
+            encoder
+                .encode_frame(Frame::new(RgbaImage::new(256, 256)))
+                .unwrap();
+        }
+        let fitted = fit_image(&UploadBytes::new(gif, "a.gif", "image/gif"), &options).unwrap();
+        let decoder = GifDecoder::new(Cursor::new(fitted.bytes())).unwrap();
+        assert!(matches!(decoder.loop_count(), LoopCount::Finite(n) if n.get() == 3));
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let bmp = encode(DynamicImage::new_rgb8(8, 8), ImageFormat::Bmp);
+        let path = std::env::temp_dir().join("telegraph-rs-transcode.bmp");
+        let size = bmp.len() as u64;
+        std::fs::write(&path, bmp).unwrap();
+        assert_eq!(path.probe().await.unwrap().size, Some(size));
+        let results = telegraph
+            .upload_files(&[&path], &UploadOptions::new().max_size(size - 1))
+            .await;
+        assert!(results[0].is_ok());
+        assert_eq!(server.uploads()[0].mime.as_deref(), Some("image/jpeg"));
+        assert_eq!(
+            server.uploads()[0].file_name.as_deref(),
+            Some("telegraph-rs-transcode.jpg")
+        );
+
+        // Accepted images are downscaled to the account's `max_dimension`.
+        let telegraph = server
+            .account("test")
+            .image_options(ImageOptions::new().max_dimension(32))
+            .create()
+            .await
+            .unwrap();
+        telegraph
+            .upload_media(&[UploadBytes::new(png, "noise.png", "image/png")])
+            .await
+            .unwrap();
+        let uploaded = image::load_from_memory(&server.uploads()[1].bytes).unwrap();
+        assert_eq!((uploaded.width(), uploaded.height()), (32, 32));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "upload")]
+    async fn strip_metadata() {
+        use crate::{
+            metadata::{strip_metadata, MetadataKind},
+            UploadBytes, UploadReader,
+        };
+
+        let segment = |marker: u8, payload: &[u8]| {
+            let mut segment = vec![0xFF, marker];
+            segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+            segment.extend_from_slice(payload);
+            segment
+        };
+        // Little-endian TIFF with a GPS IFD pointer and orientation 6.
+        let exif = b"Exif\0\0II*\0\x08\0\0\0\x02\0\x25\x88\x04\0\x01\0\0\0\x26\0\0\0\
+                     \x12\x01\x03\0\x01\0\0\0\x06\0\0\0\0\0\0\0";
+        let scan = b"\xFF\xDA\0\x02image data\xFF\xD9";
+        #[cfg(not(feature = "image-processing"))]
+        let image = [segment(0xDB, &[0; 65]).as_slice(), scan].concat();
+        // Pixels are decoded to be rotated, the image must be valid.
+        #[cfg(feature = "image-processing")]
+        let image = {
+            let mut image = vec![];
+            image::DynamicImage::new_rgb8(2, 1)
+                .write_to(
+                    &mut std::io::Cursor::new(&mut image),
+                    image::ImageFormat::Jpeg,
+                )
+                .unwrap();
+            image.split_off(2)
+        };
+        let jpeg = [
+            b"\xFF\xD8".as_slice(),
+            &segment(0xE1, exif),
+            &segment(0xE2, b"ICC_PROFILE\0\x01\x01color profile"),
+            &segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta/>"),
+            &segment(0xED, b"Photoshop 3.0\08BIM"),
+            &segment(0xFE, b"Taken by Alice"),
+            &image,
+        ]
+        .concat();
+
+        let (stripped, report) = strip_metadata(&jpeg).unwrap();
+        let kinds: Vec<_> = report.removed.iter().map(|removed| &removed.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &MetadataKind::Exif,
+                &MetadataKind::Xmp,
+                &MetadataKind::Iptc,
+                &MetadataKind::Comment
+            ]
+        );
+        assert_eq!(report.removed[3].size, 18);
+        assert_eq!(report.orientation, Some(6));
+        #[cfg(not(feature = "image-processing"))]
+        {
+            assert!(!report.rotated);
+            assert!(stripped.ends_with(&image));
+            let (again, report) = strip_metadata(&stripped).unwrap();
+            assert_eq!(again, stripped);
+            assert_eq!(report.orientation, Some(6));
+            assert_eq!(report.removed.len(), 1);
+        }
+        #[cfg(feature = "image-processing")]
+        {
+            assert!(report.rotated);
+            let rotated = image::load_from_memory(&stripped).unwrap();
+            assert_eq!((rotated.width(), rotated.height()), (1, 2));
+            // Same quantization table, so the same quality, and the same ICC profile.
+            let dqt = |jpeg: &[u8]| {
+                let i = jpeg.windows(2).position(|w| w == b"\xFF\xDB").unwrap();
+                jpeg[i..i + 69].to_vec()
+            };
+            assert_eq!(dqt(&stripped), dqt(&jpeg));
+            assert!(stripped
+                .windows(27)
+                .any(|w| w == b"ICC_PROFILE\0\x01\x01color profile"));
+            assert!(strip_metadata(&stripped).unwrap().1.is_empty());
+
+            // Converted images have no EXIF, so their orientation is applied as well.
+            use crate::transcode::{fit_image, ImageOptions};
+            let file = UploadBytes::new(jpeg.clone(), "a.jpg", "image/jpeg");
+            let options = ImageOptions::new().max_size(jpeg.len() as u64 - 1);
+            let fitted = fit_image(&file, &options).unwrap();
+            let fitted = image::load_from_memory(fitted.bytes()).unwrap();
+            assert_eq!((fitted.width(), fitted.height()), (1, 2));
+        }
+
+        let chunk = |chunk_type: &[u8], data: &[u8]| {
+            [
+                &(data.len() as u32).to_be_bytes(),
+                chunk_type,
+                data,
+                &[0; 4],
+            ]
+            .concat()
+        };
+        let header = chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        let png = [
+            b"\x89PNG\r\n\x1A\n".as_slice(),
+            &header,
+            &chunk(b"tEXt", b"Author\0Alice"),
+            &chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x:xmpmeta/>"),
+            &chunk(b"IDAT", b"data"),
+            &chunk(b"IEND", b""),
+        ]
+        .concat();
+        let (stripped, report) = strip_metadata(&png).unwrap();
+        assert_eq!(
+            stripped,
+            [
+                b"\x89PNG\r\n\x1A\n".as_slice(),
+                &header,
+                &chunk(b"IDAT", b"data"),
+                &chunk(b"IEND", b"")
+            ]
+            .concat()
+        );
+        assert_eq!(
+            report.removed[0].to_string(),
+            "removed text \"Author\" (24 bytes)"
+        );
+        assert_eq!(report.removed[1].kind, MetadataKind::Xmp);
+        assert!(matches!(
+            strip_metadata(&jpeg[..40]),
+            Err(Error::MalformedFile("jpeg"))
+        ));
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server
+            .account("test")
+            .strip_metadata(true)
+            .create()
+            .await
+            .unwrap();
+        let jpeg = [
+            b"\xFF\xD8".as_slice(),
+            &segment(0xFE, b"Taken by Alice"),
+            scan,
+        ]
+        .concat();
+        let images = telegraph
+            .upload_media(&[UploadBytes::new(jpeg, "photo.jpg", "image/jpeg")])
+            .await
+            .unwrap();
+        assert_eq!(
+            &server.uploads()[0].bytes[..],
+            &[b"\xFF\xD8".as_slice(), scan].concat()[..]
+        );
+        assert_eq!(images[0].metadata.as_ref().unwrap().removed.len(), 1);
+        let reader = UploadReader::new(std::io::Cursor::new(vec![]), "photo.jpg", "image/jpeg");
+        assert!(matches!(
+            telegraph.upload_media(&[reader]).await,
+            Err(Error::CannotStripMetadata)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "upload")]
+    async fn localize_media() {
+        use crate::MediaOptions;
+
+        let server = FakeTelegraph::start().await.unwrap();
+        let telegraph = server.account("test").create().await.unwrap();
+        let dir = std::env::temp_dir().join("telegraph-rs-localize");
+        std::fs::create_dir_all(&dir).unwrap();
+        let local = dir.join("local.png");
+        std::fs::write(&local, b"\x89PNG\r\n\x1a\nlocal image").unwrap();
+        let remote = telegraph
+            .upload_media(&[&local])
+            .await
+            .unwrap()
+            .remove(0)
+            .src;
+        // Another host name for the server, so the file is downloaded again.
+        let remote = format!(
+            "{}{}",
+            server.url().replace("127.0.0.1", "localhost"),
+            remote
+        );
+
+        let local = local.to_str().unwrap();
+        let mut content = nodes![
+            figure().image(local).caption("Local"),
+            p().child(img("data:image/gif;base64,R0lGODlh"))
+                .child(img(local)),
+            figure().video(&remote),
+            figure().image("/file/0000000000000001.png"),
+        ];
+        let options = MediaOptions::new().local_dir(&dir);
+        let localized = telegraph.localize_media(&mut content, &options).await;
+        assert!(localized.is_complete());
+        assert_eq!(localized.rewritten, 4);
+        let uploads = server.uploads();
+        assert_eq!(uploads.len(), 4);
+        assert_eq!(&uploads[2].bytes[..], b"GIF89a");
+        assert_eq!(uploads[3].mime.as_deref(), Some("image/png"));
+        assert_eq!(
+            content,
+            nodes![
+                figure().image(&uploads[1].src).caption("Local"),
+                p().child(img(&uploads[2].src)).child(img(&uploads[1].src)),
+                figure().video(&uploads[3].src),
+                figure().image("/file/0000000000000001.png"),
+            ]
+        );
+        let localized = telegraph.localize_media(&mut content, &options).await;
+        assert!(localized.is_complete());
+        assert_eq!(localized.rewritten, 0);
+
+        let mut content = nodes![img("local.png")];
+        let localized = telegraph.localize_media(&mut content, &options).await;
+        assert_eq!(localized.rewritten, 1);
+        assert_eq!(content, nodes![img(&server.uploads()[4].src)]);
+
+        std::fs::write(dir.with_file_name("telegraph-rs-outside.png"), b"secret").unwrap();
+        let mut content = nodes![img("../telegraph-rs-outside.png")];
+        let localized = telegraph.localize_media(&mut content, &options).await;
+        assert!(matches!(
+            &localized.failed[..],
+            [(_, Error::LocalMediaNotAllowed(_))]
+        ));
+
+        let mut content = nodes![img(&remote)];
+        let small = options.clone().max_download_size(4);
+        let localized = telegraph.localize_media(&mut content, &small).await;
+        assert!(matches!(
+            &localized.failed[..],
+            [(_, Error::FileTooBig { max_size: 4, .. })]
+        ));
+
+        // Failed sources are left unchanged, the others are still uploaded.
+        let mut content = nodes![
+            img(local),
+            img("data:image/gif;base64,R0lGODlh"),
+            img("data:image/png;base64,???"),
+            img(local),
+        ];
+        let localized = telegraph
+            .localize_media(&mut content, &MediaOptions::new())
+            .await;
+        assert_eq!(localized.rewritten, 1);
+        assert!(matches!(
+            &localized.failed[..],
+            [(first, Error::LocalMediaNotAllowed(_)), (second, Error::InvalidMedia(_))]
+                if first == local && second == "data:image/png;base64,???"
+        ));
+        assert_eq!(
+            content,
+            nodes![
+                img(local),
+                img(&server.uploads()[5].src),
+                img("data:image/png;base64,???"),
+                img(local),
+            ]
+        );
+
+        assert_eq!(
+            crate::media::MediaSource::parse("//example.com/a.png", server.url()).unwrap(),
+            crate::media::MediaSource::Remote("https://example.com/a.png".parse().unwrap())
+        );
     }
 }
@@ -0,0 +1,377 @@
+//! Conversion of Markdown into page content.
+use crate::{render::render_table, Node, NodeElement, Result};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::{collections::HashMap, mem, ops::Range};
+
+/// Convert CommonMark with GitHub extensions into Telegraph nodes.
+///
+/// Markdown constructs are mapped onto the closest tags Telegraph supports:
+///
+/// - `#` and `##` headings become `h3`, deeper headings `h4`
+/// - fenced and indented code blocks become `pre` with a `code` child
+/// - an image in a paragraph becomes a `figure` with the alt text as `figcaption`
+/// - `~~strikethrough~~` becomes `s`
+/// - task list items start with `☐` or `☑`
+/// - tables become `pre` with aligned columns
+///
+/// Raw HTML, such as the `aside`, `u` and embeds written by
+/// [`nodes_to_markdown`](crate::nodes_to_markdown), is converted with
+/// [`convert_html`](crate::convert::convert_html). Without the `kuchiki` or
+/// `html` feature there is no HTML parser and raw HTML other than comments
+/// fails with [`Error::UnsupportedHtml`](crate::Error::UnsupportedHtml).
+///
+/// ```
+/// use telegraph_rs::{builder::*, markdown_to_nodes, nodes};
+///
+/// let content = markdown_to_nodes("# Title\n\nSome *text*.")?;
+/// assert_eq!(content, nodes![h3().text("Title"), p().text("Some ").child(em().text("text")).text(".")]);
+/// # Ok::<_, telegraph_rs::Error>(())
+/// ```
+pub fn markdown_to_nodes(markdown: &str) -> Result<Vec<Node>> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_GFM;
+    let mut converter = Converter {
+        source: markdown,
+        stack: vec![element("")],
+        table: None,
+        html: String::new(),
+        inline_html: None,
+    };
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        converter.event(event, range)?;
+    }
+    Ok(converter.stack.pop().unwrap().children.unwrap_or_default())
+}
+
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+fn convert_html(html: &str) -> Result<Vec<Node>> {
+    Ok(crate::convert_html(html, &crate::convert::ConvertOptions::new())?.nodes)
+}
+
+#[cfg(not(any(feature = "kuchiki", feature = "html")))]
+fn convert_html(html: &str) -> Result<Vec<Node>> {
+    let html = html.trim();
+    if html.starts_with("<!--") && html.ends_with("-->") {
+        Ok(vec![])
+    } else {
+        Err(crate::Error::UnsupportedHtml(html.to_owned()))
+    }
+}
+
+/// How much `tag`, a single inline HTML tag, changes the number of open elements.
+fn html_depth(tag: &str) -> isize {
+    const VOID: &[&str] = &[
+        "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source",
+        "track", "wbr",
+    ];
+    let name: String = tag
+        .trim_start_matches('<')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    if tag.starts_with("</") {
+        -1
+    } else if name.is_empty() || tag.ends_with("/>") || VOID.contains(&&*name.to_ascii_lowercase())
+    {
+        0
+    } else {
+        1
+    }
+}
+
+fn element(tag: &str) -> NodeElement {
+    NodeElement {
+        tag: tag.to_owned(),
+        attrs: None,
+        children: None,
+    }
+}
+
+fn element_with(tag: &str, attr: &str, value: &str) -> NodeElement {
+    let mut attrs = HashMap::new();
+    attrs.insert(attr.to_owned(), Some(value.to_owned()));
+    NodeElement {
+        attrs: Some(attrs),
+        ..element(tag)
+    }
+}
+
+/// Concatenated text of `nodes`.
+fn text_of(nodes: &[Node], text: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::NodeElement(element) => {
+                text_of(element.children.as_deref().unwrap_or_default(), text)
+            }
+        }
+    }
+}
+
+/// Cells of a table being converted.
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    head_rows: usize,
+    cell: Option<String>,
+}
+
+/// Inline HTML whose elements are not closed yet.
+struct InlineHtml {
+    /// Span of the HTML in the source.
+    range: Range<usize>,
+    /// Number of open HTML elements.
+    open: isize,
+    /// Number of Markdown elements started inside the HTML.
+    nested: usize,
+}
+
+struct Converter<'a> {
+    source: &'a str,
+    /// Elements being built, the first one holds the top-level nodes.
+    ///
+    /// Elements with an empty tag are unwrapped into their parent when closed.
+    stack: Vec<NodeElement>,
+    table: Option<Table>,
+    /// Content of the HTML block being read.
+    html: String,
+    inline_html: Option<InlineHtml>,
+}
+
+impl Converter<'_> {
+    fn event(&mut self, event: Event<'_>, range: Range<usize>) -> Result<()> {
+        if let Some(html) = &mut self.inline_html {
+            // Everything up to the closing tags is converted as HTML, unless the
+            // Markdown element containing the HTML ends first.
+            match &event {
+                Event::End(_) if html.nested == 0 => self.close_inline_html()?,
+                event => {
+                    match event {
+                        Event::InlineHtml(tag) => html.open += html_depth(tag),
+                        Event::Start(_) => html.nested += 1,
+                        Event::End(_) => html.nested -= 1,
+                        _ => {}
+                    }
+                    html.range.end = html.range.end.max(range.end);
+                    if html.open <= 0 {
+                        self.close_inline_html()?;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(table) = &mut self.table {
+            match event {
+                Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => {
+                    table.rows.push(vec![])
+                }
+                Event::End(TagEnd::TableHead) => table.head_rows = table.rows.len(),
+                Event::Start(Tag::TableCell) => table.cell = Some(String::new()),
+                Event::End(TagEnd::TableCell) => {
+                    let cell = table.cell.take().unwrap_or_default();
+                    table.rows.last_mut().unwrap().push(cell.trim().to_owned());
+                }
+                Event::Text(text)
+                | Event::Code(text)
+                | Event::InlineMath(text)
+                | Event::InlineHtml(text) => {
+                    if let Some(cell) = &mut table.cell {
+                        cell.push_str(&text);
+                    }
+                }
+                Event::End(TagEnd::Table) => {
+                    let table = self.table.take().unwrap();
+                    let mut pre = element("pre");
+                    pre.children =
+                        Some(vec![Node::Text(render_table(&table.rows, table.head_rows))]);
+                    self.push(pre.into());
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag)?,
+            Event::Text(text) => self.push(Node::Text(text.into_string())),
+            Event::Code(code) | Event::InlineMath(code) => {
+                let mut element = element("code");
+                element.children = Some(vec![Node::Text(code.into_string())]);
+                self.push(element.into());
+            }
+            Event::DisplayMath(math) => {
+                let mut element = element("pre");
+                element.children = Some(vec![Node::Text(math.into_string())]);
+                self.push(element.into());
+            }
+            Event::FootnoteReference(name) => self.push(Node::Text(format!("[{}]", name))),
+            Event::SoftBreak => self.push(Node::Text(" ".to_owned())),
+            Event::HardBreak => self.push(element("br").into()),
+            Event::Rule => self.push(element("hr").into()),
+            Event::TaskListMarker(checked) => {
+                self.push(Node::Text(if checked { "☑ " } else { "☐ " }.to_owned()))
+            }
+            Event::Html(html) => self.html.push_str(&html),
+            Event::InlineHtml(tag) => {
+                self.inline_html = Some(InlineHtml {
+                    range,
+                    open: html_depth(&tag),
+                    nested: 0,
+                });
+                if html_depth(&tag) <= 0 {
+                    self.close_inline_html()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close_inline_html(&mut self) -> Result<()> {
+        let html = self.inline_html.take().unwrap();
+        for node in convert_html(&self.source[html.range])? {
+            self.push(node);
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        let element = match tag {
+            Tag::Paragraph => element("p"),
+            Tag::Heading { level, .. } => match level {
+                HeadingLevel::H1 | HeadingLevel::H2 => element("h3"),
+                _ => element("h4"),
+            },
+            Tag::BlockQuote(_) => element("blockquote"),
+            Tag::CodeBlock(_) => {
+                self.stack.push(element("pre"));
+                element("code")
+            }
+            Tag::List(Some(_)) => element("ol"),
+            Tag::List(None) => element("ul"),
+            Tag::Item => element("li"),
+            Tag::Table(_) => {
+                self.table = Some(Table::default());
+                return;
+            }
+            Tag::Emphasis => element("em"),
+            Tag::Strong => element("strong"),
+            Tag::Strikethrough => element("s"),
+            Tag::Link { dest_url, .. } => element_with("a", "href", &dest_url),
+            Tag::Image { dest_url, .. } => element_with("img", "src", &dest_url),
+            _ => element(""),
+        };
+        self.stack.push(element);
+    }
+
+    fn end(&mut self, tag: TagEnd) -> Result<()> {
+        let mut closed = self.stack.pop().unwrap();
+        match tag {
+            TagEnd::HtmlBlock => {
+                for node in convert_html(&mem::take(&mut self.html))? {
+                    self.push(node);
+                }
+            }
+            TagEnd::CodeBlock => {
+                let mut pre = self.stack.pop().unwrap();
+                pre.children = Some(vec![closed.into()]);
+                self.push(pre.into());
+            }
+            TagEnd::Image => {
+                let mut alt = String::new();
+                text_of(&closed.children.take().unwrap_or_default(), &mut alt);
+                if self.top().tag != "p" {
+                    self.push(closed.into());
+                    return Ok(());
+                }
+                let mut figure = element("figure");
+                let mut children = vec![closed.into()];
+                if !alt.is_empty() {
+                    let mut caption = element("figcaption");
+                    caption.children = Some(vec![Node::Text(alt)]);
+                    children.push(caption.into());
+                }
+                figure.children = Some(children);
+                self.push(figure.into());
+            }
+            TagEnd::Paragraph => self.close_paragraph(closed),
+            TagEnd::BlockQuote(_) => {
+                let children = closed.children.as_deref().unwrap_or_default();
+                let paragraphs: Option<Vec<_>> = children
+                    .iter()
+                    .map(|child| match child {
+                        Node::NodeElement(p) if p.tag == "p" => {
+                            Some(p.children.clone().unwrap_or_default())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                // Telegraph quotes hold inline content, join paragraphs with line breaks.
+                if let Some(paragraphs) = paragraphs {
+                    let mut joined = vec![];
+                    for (i, paragraph) in paragraphs.into_iter().enumerate() {
+                        if i > 0 {
+                            joined.push(element("br").into());
+                        }
+                        joined.extend(paragraph);
+                    }
+                    closed.children = Some(joined);
+                }
+                self.push(closed.into());
+            }
+            _ if closed.tag.is_empty() => {
+                for child in closed.children.unwrap_or_default() {
+                    self.push(child);
+                }
+            }
+            _ => self.push(closed.into()),
+        }
+        Ok(())
+    }
+
+    /// Append a paragraph, moving its figures out of it.
+    fn close_paragraph(&mut self, paragraph: NodeElement) {
+        let mut inline = vec![];
+        for child in paragraph.children.unwrap_or_default() {
+            match child {
+                Node::NodeElement(figure) if figure.tag == "figure" => {
+                    self.push_paragraph(mem::take(&mut inline));
+                    self.push(figure.into());
+                }
+                child => inline.push(child),
+            }
+        }
+        self.push_paragraph(inline);
+    }
+
+    fn push_paragraph(&mut self, mut children: Vec<Node>) {
+        if let Some(Node::Text(text)) = children.first_mut() {
+            *text = text.trim_start().to_owned();
+        }
+        if let Some(Node::Text(text)) = children.last_mut() {
+            *text = text.trim_end().to_owned();
+        }
+        children.retain(|child| !matches!(child, Node::Text(text) if text.is_empty()));
+        if !children.is_empty() {
+            let mut paragraph = element("p");
+            paragraph.children = Some(children);
+            self.push(paragraph.into());
+        }
+    }
+
+    fn top(&mut self) -> &mut NodeElement {
+        self.stack.last_mut().unwrap()
+    }
+
+    /// Append `node` to the innermost element, merging adjacent text.
+    fn push(&mut self, node: Node) {
+        let children = self.top().children.get_or_insert_with(Vec::new);
+        match (children.last_mut(), node) {
+            (Some(Node::Text(last)), Node::Text(text)) => last.push_str(&text),
+            (_, node) => children.push(node),
+        }
+    }
+}
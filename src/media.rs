@@ -0,0 +1,245 @@
+//! Uploading the media referenced by page content.
+use crate::{guess_mime, Error, Node, NodeElement, Result, UploadBytes, UploadOptions};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{header::CONTENT_TYPE, Client, Response, Url};
+use std::path::{Path, PathBuf};
+
+/// Tags whose `src` is uploaded by [`Telegraph::localize_media`](crate::Telegraph::localize_media).
+pub const MEDIA_TAGS: &[&str] = &["img", "video"];
+
+/// What [`Telegraph::localize_media`](crate::Telegraph::localize_media) did.
+#[derive(Debug, Default)]
+pub struct LocalizedMedia {
+    /// Number of rewritten `src` attributes.
+    pub rewritten: usize,
+    /// Sources that could not be read or uploaded, left unchanged, with the reason.
+    pub failed: Vec<(String, Error)>,
+}
+
+impl LocalizedMedia {
+    /// Whether every source was uploaded or already on the upload host.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Options of [`Telegraph::localize_media`](crate::Telegraph::localize_media).
+#[derive(Debug, Clone)]
+pub struct MediaOptions {
+    pub(crate) local_dir: Option<PathBuf>,
+    pub(crate) max_download_size: u64,
+    pub(crate) upload: UploadOptions,
+}
+
+impl Default for MediaOptions {
+    fn default() -> Self {
+        MediaOptions {
+            local_dir: None,
+            #[cfg(not(feature = "image-processing"))]
+            max_download_size: crate::MAX_UPLOAD_SIZE,
+            // Bigger images may still be converted to fit.
+            #[cfg(feature = "image-processing")]
+            max_download_size: crate::transcode::MAX_INPUT_SIZE,
+            upload: UploadOptions::default(),
+        }
+    }
+}
+
+impl MediaOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow reading the files under `dir`, against which relative paths are resolved.
+    ///
+    /// Local paths and `file://` URLs are rejected with [`Error::LocalMediaNotAllowed`]
+    /// by default, as the content may come from someone who shouldn't read your disk.
+    pub fn local_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.local_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Stop downloading remote files bigger than `max_size` bytes.
+    ///
+    /// [`MAX_UPLOAD_SIZE`](crate::MAX_UPLOAD_SIZE) by default, or `transcode::MAX_INPUT_SIZE`
+    /// with the `image-processing` feature.
+    pub fn max_download_size(mut self, max_size: u64) -> Self {
+        self.max_download_size = max_size;
+        self
+    }
+
+    /// Upload the files with `options`.
+    pub fn upload(mut self, options: UploadOptions) -> Self {
+        self.upload = options;
+        self
+    }
+
+    /// `path` resolved against the local directory, if it is inside it.
+    async fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        let not_allowed = || Error::LocalMediaNotAllowed(path.to_owned());
+        let dir = self.local_dir.as_ref().ok_or_else(not_allowed)?;
+        let dir = tokio::fs::canonicalize(dir).await?;
+        // Following `..` and symbolic links before checking the prefix.
+        let resolved = tokio::fs::canonicalize(dir.join(path)).await?;
+        if resolved.starts_with(&dir) {
+            Ok(resolved)
+        } else {
+            Err(not_allowed())
+        }
+    }
+}
+
+/// Where the file behind a `src` comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MediaSource {
+    /// Already served by the upload host.
+    Uploaded,
+    /// A file on disk.
+    Local(PathBuf),
+    /// Content of a `data:` URI.
+    Data { mime: String, bytes: Vec<u8> },
+    /// An `http` or `https` URL on another host.
+    Remote(Url),
+}
+
+impl MediaSource {
+    /// Classify `src`, `upload_url` being the base URL files are served from.
+    pub(crate) fn parse(src: &str, upload_url: &str) -> Result<Self> {
+        let src = src.trim();
+        if src.starts_with("/file/")
+            || src
+                .strip_prefix(upload_url)
+                .is_some_and(|path| path.starts_with("/file/"))
+        {
+            return Ok(MediaSource::Uploaded);
+        }
+        if let Some(data) = src.strip_prefix("data:") {
+            return parse_data_uri(data);
+        }
+        // Protocol-relative URLs, as pages are served over https.
+        if let Some(rest) = src.strip_prefix("//") {
+            return Url::parse(&format!("https://{}", rest))
+                .map(MediaSource::Remote)
+                .map_err(|_| Error::InvalidMedia(src.to_owned()));
+        }
+        match Url::parse(src) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+                Ok(MediaSource::Remote(url))
+            }
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map(MediaSource::Local)
+                .map_err(|_| Error::InvalidMedia(src.to_owned())),
+            // Windows paths such as `C:\image.png` parse as URLs with a one-letter scheme.
+            Ok(url) if url.scheme().len() > 1 => Err(Error::InvalidMedia(src.to_owned())),
+            _ => Ok(MediaSource::Local(PathBuf::from(src))),
+        }
+    }
+
+    /// Read the file into memory, downloading it with `client` if remote.
+    pub(crate) async fn fetch(
+        self,
+        client: &Client,
+        options: &MediaOptions,
+    ) -> Result<Option<UploadBytes>> {
+        Ok(Some(match self {
+            MediaSource::Uploaded => return Ok(None),
+            MediaSource::Local(path) => {
+                let path = options.resolve(&path).await?;
+                let bytes = tokio::fs::read(&path).await?;
+                let file_name = path
+                    .file_name()
+                    .map_or_else(|| "file".into(), |name| name.to_string_lossy());
+                UploadBytes::new(bytes, &file_name, &guess_mime(&path))
+            }
+            MediaSource::Data { mime, bytes } => UploadBytes::new(bytes, "file", &mime),
+            MediaSource::Remote(url) => {
+                let response = client
+                    .get(url.clone())
+                    .send()
+                    .await
+                    .and_then(Response::error_for_status)?;
+                let name = url
+                    .path_segments()
+                    .and_then(|mut segments| segments.next_back())
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_owned);
+                let mime = response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|mime| mime.to_str().ok())
+                    .and_then(|mime| mime.split(';').next())
+                    .map(|mime| mime.trim().to_owned())
+                    .unwrap_or_else(|| guess_mime(name.as_deref().unwrap_or_default()));
+                let bytes = download(response, options.max_download_size).await?;
+                UploadBytes::new(bytes, name.as_deref().unwrap_or("file"), &mime)
+            }
+        }))
+    }
+}
+
+/// Read the body of `response`, failing once it exceeds `max_size` bytes.
+async fn download(mut response: Response, max_size: u64) -> Result<Vec<u8>> {
+    let too_big = |size| Error::FileTooBig { size, max_size };
+    if let Some(size) = response.content_length().filter(|size| *size > max_size) {
+        return Err(too_big(size));
+    }
+    let mut bytes = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() as u64 > max_size {
+            return Err(too_big(bytes.len() as u64));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Parse what follows `data:` in a data URI.
+fn parse_data_uri(data: &str) -> Result<MediaSource> {
+    let invalid = || Error::InvalidMedia(format!("data:{}", data));
+    let (header, content) = data.split_once(',').ok_or_else(invalid)?;
+    let (mime, base64) = match header.strip_suffix(";base64") {
+        Some(mime) => (mime, true),
+        None => (header, false),
+    };
+    // Parameters such as the charset are not needed to upload the file.
+    let mime = mime.split(';').next().unwrap_or_default().trim();
+    let mime = if mime.is_empty() { "text/plain" } else { mime };
+    let content: Vec<u8> = percent_encoding::percent_decode_str(content).collect();
+    let bytes = if base64 {
+        let content: Vec<u8> = content
+            .into_iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        STANDARD.decode(content).map_err(|_| invalid())?
+    } else {
+        content
+    };
+    Ok(MediaSource::Data {
+        mime: mime.to_owned(),
+        bytes,
+    })
+}
+
+/// Mutable references to the `src` of every media element in `nodes`.
+pub(crate) fn media_srcs(nodes: &mut [Node]) -> Vec<&mut String> {
+    let mut srcs = vec![];
+    for node in nodes {
+        if let Node::NodeElement(NodeElement {
+            tag,
+            attrs,
+            children,
+        }) = node
+        {
+            if MEDIA_TAGS.contains(&tag.as_str()) {
+                if let Some(Some(src)) = attrs.as_mut().and_then(|attrs| attrs.get_mut("src")) {
+                    srcs.push(src);
+                }
+            }
+            if let Some(children) = children {
+                srcs.extend(media_srcs(children));
+            }
+        }
+    }
+    srcs
+}
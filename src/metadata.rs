@@ -0,0 +1,430 @@
+//! Removing metadata from images before they are published.
+//!
+//! EXIF, XMP and IPTC blocks often hold GPS coordinates, camera serial numbers or the
+//! name of the author. [`strip_metadata`] removes them from JPEG and PNG files, keeping
+//! what is needed to display the image: JFIF and Adobe segments, ICC profiles and the
+//! orientation.
+use crate::{upload::sniff_mime, Error, Result};
+use std::fmt;
+
+/// Kind of metadata removed from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataKind {
+    Exif,
+    Xmp,
+    Iptc,
+    /// A JPEG comment.
+    Comment,
+    /// A PNG text chunk, with its keyword.
+    Text(String),
+    /// Another JPEG segment or PNG chunk, e.g. `APP12` or `tIME`.
+    Other(String),
+}
+
+impl fmt::Display for MetadataKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataKind::Exif => f.write_str("EXIF"),
+            MetadataKind::Xmp => f.write_str("XMP"),
+            MetadataKind::Iptc => f.write_str("IPTC"),
+            MetadataKind::Comment => f.write_str("comment"),
+            MetadataKind::Text(keyword) => write!(f, "text {:?}", keyword),
+            MetadataKind::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A block of metadata removed from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedMetadata {
+    pub kind: MetadataKind,
+    /// Size of the block in bytes.
+    pub size: usize,
+}
+
+impl fmt::Display for RemovedMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "removed {} ({} bytes)", self.kind, self.size)
+    }
+}
+
+/// What [`strip_metadata`] did to a file.
+///
+/// Without the `image-processing` feature, the pixels of a file with a non-default
+/// orientation aren't rotated: its EXIF block is replaced by a new one holding only the
+/// orientation, which is not reported in `removed`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataReport {
+    /// Removed metadata, in the order it was found.
+    pub removed: Vec<RemovedMetadata>,
+    /// EXIF orientation of the file, if not the default one.
+    pub orientation: Option<u16>,
+    /// Whether the pixels were rotated according to `orientation`.
+    ///
+    /// Otherwise an EXIF block holding only the orientation was written in place of the
+    /// original one.
+    pub rotated: bool,
+}
+
+impl MetadataReport {
+    /// Whether nothing was removed.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+}
+
+/// Remove EXIF, XMP and IPTC metadata, comments and text from a JPEG or PNG file.
+///
+/// A file with a non-default EXIF orientation is rotated with the `image-processing`
+/// feature, which re-encodes it with its ICC profile and, for JPEG, its estimated quality.
+/// Without it, the orientation is written back in a minimal EXIF block. Other files are
+/// returned unchanged.
+///
+/// ```
+/// use telegraph_rs::metadata::strip_metadata;
+///
+/// let jpeg = b"\xFF\xD8\xFF\xFE\x00\x07hello\xFF\xDA\x00\x02\xFF\xD9";
+/// let (stripped, report) = strip_metadata(jpeg)?;
+/// assert_eq!(stripped, b"\xFF\xD8\xFF\xDA\x00\x02\xFF\xD9");
+/// assert_eq!(report.removed[0].to_string(), "removed comment (9 bytes)");
+/// # Ok::<(), telegraph_rs::Error>(())
+/// ```
+pub fn strip_metadata(bytes: &[u8]) -> Result<(Vec<u8>, MetadataReport)> {
+    let (stripped, report) = match sniff_mime(bytes) {
+        Some("image/jpeg") => strip_jpeg(bytes)?,
+        Some("image/png") => strip_png(bytes)?,
+        _ => return Ok((bytes.to_vec(), MetadataReport::default())),
+    };
+    #[cfg(feature = "image-processing")]
+    if let Some(orientation) = report.orientation {
+        return Ok((
+            rotate(bytes, orientation)?,
+            MetadataReport {
+                rotated: true,
+                ..report
+            },
+        ));
+    }
+    Ok((stripped, report))
+}
+
+/// Apply `orientation` to the pixels of `bytes` and encode them again, keeping only the
+/// ICC profile.
+///
+/// JPEG files are encoded with the quality they were encoded with, as estimated by
+/// [`jpeg_quality`].
+#[cfg(feature = "image-processing")]
+fn rotate(bytes: &[u8], orientation: u16) -> Result<Vec<u8>> {
+    use image::{
+        codecs::{jpeg::JpegEncoder, png::PngEncoder},
+        metadata::Orientation,
+        DynamicImage, ImageDecoder, ImageEncoder, ImageError, ImageFormat, ImageReader,
+    };
+    use std::io::Cursor;
+
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let format = reader.format();
+    let mut decoder = reader.into_decoder()?;
+    let icc_profile = decoder.icc_profile()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    if let Some(orientation) = Orientation::from_exif(orientation as u8) {
+        image.apply_orientation(orientation);
+    }
+    fn encode(
+        image: &DynamicImage,
+        mut encoder: impl ImageEncoder,
+        icc_profile: Option<Vec<u8>>,
+    ) -> Result<()> {
+        if let Some(icc_profile) = icc_profile {
+            encoder
+                .set_icc_profile(icc_profile)
+                .map_err(ImageError::Unsupported)?;
+        }
+        Ok(image.write_with_encoder(encoder)?)
+    }
+
+    let mut rotated = vec![];
+    if format == Some(ImageFormat::Jpeg) {
+        let quality = jpeg_quality(bytes).unwrap_or(DEFAULT_JPEG_QUALITY);
+        let encoder = JpegEncoder::new_with_quality(&mut rotated, quality);
+        encode(&image, encoder, icc_profile)?;
+    } else {
+        encode(&image, PngEncoder::new(&mut rotated), icc_profile)?;
+    }
+    Ok(rotated)
+}
+
+/// Quality of rotated JPEG files whose quality can't be estimated.
+#[cfg(feature = "image-processing")]
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Luminance quantization table of the JPEG standard, scaled by encoders for a quality.
+#[cfg(feature = "image-processing")]
+const STANDARD_LUMINANCE_TABLE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// Quality a JPEG file was encoded with, estimated from its first quantization table
+/// the way the IJG encoder scales the standard one.
+#[cfg(feature = "image-processing")]
+fn jpeg_quality(bytes: &[u8]) -> Option<u8> {
+    let mut i = 2;
+    while bytes.get(i) == Some(&0xFF) {
+        let marker = *bytes.get(i + 1)?;
+        match marker {
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            0x01 | 0xD0..=0xD7 => {
+                i += 2;
+                continue;
+            }
+            0xDA | 0xD9 => return None,
+            _ => {}
+        }
+        let length = u16::from_be_bytes([*bytes.get(i + 2)?, *bytes.get(i + 3)?]) as usize;
+        let payload = bytes.get(i + 4..i + 2 + length)?;
+        if marker == 0xDB {
+            // 8-bit or 16-bit values, after the precision and identifier of the table.
+            let sum: u32 = if payload.first()? >> 4 == 0 {
+                payload.get(1..65)?.iter().map(|q| *q as u32).sum()
+            } else {
+                payload
+                    .get(1..129)?
+                    .chunks(2)
+                    .map(|q| u16::from_be_bytes([q[0], q[1]]) as u32)
+                    .sum()
+            };
+            let standard: u32 = STANDARD_LUMINANCE_TABLE.iter().map(|q| *q as u32).sum();
+            let scale = sum as f64 * 100.0 / standard as f64;
+            let quality = if scale <= 100.0 {
+                (200.0 - scale) / 2.0
+            } else {
+                5000.0 / scale
+            };
+            return Some(quality.round().clamp(1.0, 100.0) as u8);
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADERS: &[&[u8]] = &[
+    b"http://ns.adobe.com/xap/1.0/\0",
+    b"http://ns.adobe.com/xmp/extension/\0",
+];
+/// Segments kept in a JPEG file, identified by the start of their payload.
+const KEPT_SEGMENTS: &[(u8, &[u8])] = &[
+    (0xE0, b"JFIF\0"),
+    (0xE0, b"JFXX\0"),
+    (0xE2, b"ICC_PROFILE\0"),
+    (0xEE, b"Adobe"),
+];
+
+fn strip_jpeg(bytes: &[u8]) -> Result<(Vec<u8>, MetadataReport)> {
+    let malformed = || Error::MalformedFile("jpeg");
+    let mut stripped = bytes[..2].to_vec();
+    let mut report = MetadataReport::default();
+    let mut i = 2;
+    loop {
+        if bytes.get(i) != Some(&0xFF) {
+            return Err(malformed());
+        }
+        let marker = *bytes.get(i + 1).ok_or_else(malformed)?;
+        match marker {
+            // Fill byte.
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            // Standalone markers.
+            0x01 | 0xD0..=0xD7 => {
+                stripped.extend_from_slice(&bytes[i..i + 2]);
+                i += 2;
+                continue;
+            }
+            // Start of scan or end of image, only image data follows.
+            0xDA | 0xD9 => {
+                stripped.extend_from_slice(&bytes[i..]);
+                break;
+            }
+            _ => {}
+        }
+        let length = bytes
+            .get(i + 2..i + 4)
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+            .filter(|length| *length >= 2)
+            .ok_or_else(malformed)?;
+        let segment = bytes.get(i..i + 2 + length).ok_or_else(malformed)?;
+        let payload = &segment[4..];
+        i += segment.len();
+
+        let kind = match marker {
+            0xE1 if payload.starts_with(EXIF_HEADER) => {
+                let orientation = exif_orientation(&payload[EXIF_HEADER.len()..]);
+                if let Some(orientation) = orientation.filter(|o| *o != 1) {
+                    report.orientation = Some(orientation);
+                    let exif = [EXIF_HEADER, &orientation_exif(orientation)].concat();
+                    stripped.extend_from_slice(&[0xFF, 0xE1]);
+                    stripped.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+                    stripped.extend_from_slice(&exif);
+                }
+                MetadataKind::Exif
+            }
+            0xE1 if XMP_HEADERS.iter().any(|header| payload.starts_with(header)) => {
+                MetadataKind::Xmp
+            }
+            0xED if payload.starts_with(b"Photoshop 3.0\0") => MetadataKind::Iptc,
+            0xFE => MetadataKind::Comment,
+            0xE0..=0xEF
+                if !KEPT_SEGMENTS
+                    .iter()
+                    .any(|(m, header)| *m == marker && payload.starts_with(header)) =>
+            {
+                MetadataKind::Other(format!("APP{}", marker - 0xE0))
+            }
+            _ => {
+                stripped.extend_from_slice(segment);
+                continue;
+            }
+        };
+        report.removed.push(RemovedMetadata {
+            kind,
+            size: segment.len(),
+        });
+    }
+    Ok((stripped, report))
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\n";
+
+fn strip_png(bytes: &[u8]) -> Result<(Vec<u8>, MetadataReport)> {
+    let malformed = || Error::MalformedFile("png");
+    let mut stripped = PNG_SIGNATURE.to_vec();
+    let mut report = MetadataReport::default();
+    let mut i = PNG_SIGNATURE.len();
+    while i < bytes.len() {
+        let length = bytes
+            .get(i..i + 4)
+            .map(|length| u32::from_be_bytes([length[0], length[1], length[2], length[3]]))
+            .ok_or_else(malformed)? as usize;
+        // Length, type, data and CRC.
+        let chunk = bytes.get(i..i + 12 + length).ok_or_else(malformed)?;
+        let chunk_type = &chunk[4..8];
+        let data = &chunk[8..8 + length];
+        i += chunk.len();
+
+        let kind = match chunk_type {
+            b"eXIf" => {
+                if let Some(orientation) = exif_orientation(data).filter(|o| *o != 1) {
+                    report.orientation = Some(orientation);
+                    stripped.extend_from_slice(&png_chunk(b"eXIf", &orientation_exif(orientation)));
+                }
+                MetadataKind::Exif
+            }
+            b"tEXt" | b"zTXt" | b"iTXt" => {
+                let keyword = data.split(|b| *b == 0).next().unwrap_or_default();
+                let keyword = String::from_utf8_lossy(keyword).into_owned();
+                let lowercase = keyword.to_lowercase();
+                if keyword == "XML:com.adobe.xmp" || lowercase == "raw profile type xmp" {
+                    MetadataKind::Xmp
+                } else if lowercase == "raw profile type exif"
+                    || lowercase == "raw profile type app1"
+                {
+                    MetadataKind::Exif
+                } else if lowercase == "raw profile type iptc"
+                    || lowercase == "raw profile type 8bim"
+                {
+                    MetadataKind::Iptc
+                } else {
+                    MetadataKind::Text(keyword)
+                }
+            }
+            b"tIME" => MetadataKind::Other("tIME".to_owned()),
+            _ => {
+                stripped.extend_from_slice(chunk);
+                continue;
+            }
+        };
+        report.removed.push(RemovedMetadata {
+            kind,
+            size: chunk.len(),
+        });
+    }
+    Ok((stripped, report))
+}
+
+/// Valid orientation stored in the first IFD of a TIFF structure.
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |i: usize| {
+        let bytes = [*tiff.get(i)?, *tiff.get(i + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |i: usize| {
+        let bytes = [
+            *tiff.get(i)?,
+            *tiff.get(i + 1)?,
+            *tiff.get(i + 2)?,
+            *tiff.get(i + 3)?,
+        ];
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|n| ifd + 2 + n * 12)
+        .find(|entry| u16_at(*entry) == Some(0x0112))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// A big-endian TIFF structure holding only `orientation`.
+fn orientation_exif(orientation: u16) -> Vec<u8> {
+    let mut tiff = b"MM\0*\0\0\0\x08".to_vec();
+    // One entry: orientation, a SHORT, one value.
+    tiff.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    // Padding of the value and offset of the next IFD.
+    tiff.extend_from_slice(&[0; 6]);
+    tiff
+}
+
+fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,201 @@
+//! Client-side rate limiting with token buckets.
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Kind of request, each kind is limited by its own budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    /// Methods that change state: createAccount, editAccountInfo, revokeAccessToken,
+    /// createPage and editPage.
+    Write,
+    /// Methods that only read: getAccountInfo, getPage, getPageList and getViews.
+    Read,
+    /// File uploads.
+    Upload,
+}
+
+impl RequestKind {
+    /// Kind of the API method `method`.
+    pub fn of(method: &str) -> Self {
+        match method {
+            "createAccount" | "editAccountInfo" | "revokeAccessToken" | "createPage"
+            | "editPage" => RequestKind::Write,
+            "upload" => RequestKind::Upload,
+            _ => RequestKind::Read,
+        }
+    }
+}
+
+/// Number of requests allowed in a period of time.
+///
+/// Up to `burst` requests are sent at once, then one every `period / requests`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    interval: Duration,
+    burst: u32,
+}
+
+impl Quota {
+    /// `requests` per `period`, with a burst of `requests`.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        let requests = requests.max(1);
+        Quota {
+            interval: period / requests,
+            burst: requests,
+        }
+    }
+
+    /// `requests` per second.
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// `requests` per minute.
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Maximum number of requests sent at once after being idle.
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+/// Budgets for each [`RequestKind`], `None` means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimit {
+    writes: Option<Quota>,
+    reads: Option<Quota>,
+    uploads: Option<Quota>,
+}
+
+impl RateLimit {
+    /// No limits at all, add them with the other methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Budget of account and page writes.
+    pub fn writes(mut self, quota: Quota) -> Self {
+        self.writes = Some(quota);
+        self
+    }
+
+    /// Budget of reads.
+    pub fn reads(mut self, quota: Quota) -> Self {
+        self.reads = Some(quota);
+        self
+    }
+
+    /// Budget of uploads.
+    pub fn uploads(mut self, quota: Quota) -> Self {
+        self.uploads = Some(quota);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(quota: Quota) -> Self {
+        Bucket {
+            quota,
+            tokens: quota.burst as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Take a token, or return how long to wait until one is available.
+    fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated);
+        self.updated = now;
+        if self.quota.interval.is_zero() {
+            return Ok(());
+        }
+        self.tokens = (self.tokens + elapsed.as_secs_f64() / self.quota.interval.as_secs_f64())
+            .min(self.quota.burst as f64);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.quota.interval.mul_f64(1.0 - self.tokens))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    writes: Option<Mutex<Bucket>>,
+    reads: Option<Mutex<Bucket>>,
+    uploads: Option<Mutex<Bucket>>,
+}
+
+/// Token-bucket rate limiter.
+///
+/// Clones share the same budgets, so a single limiter can be shared between
+/// several [`Telegraph`](crate::Telegraph) accounts to enforce a global limit.
+///
+/// ```
+/// use telegraph_rs::{Quota, RateLimit, RateLimiter, Telegraph};
+///
+/// let global = RateLimiter::new(RateLimit::new().writes(Quota::per_minute(60)));
+/// let account = Telegraph::new("author")
+///     .rate_limit(RateLimit::new().writes(Quota::per_second(1)).reads(Quota::per_second(5)))
+///     .global_rate_limiter(global.clone());
+/// ```
+#[derive(Clone)]
+pub struct RateLimiter {
+    buckets: Arc<Buckets>,
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("buckets", &Arc::as_ptr(&self.buckets))
+            .finish()
+    }
+}
+
+impl RateLimiter {
+    /// Create a limiter with the budgets of `limit`.
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            buckets: Arc::new(Buckets {
+                writes: limit.writes.map(|q| Mutex::new(Bucket::new(q))),
+                reads: limit.reads.map(|q| Mutex::new(Bucket::new(q))),
+                uploads: limit.uploads.map(|q| Mutex::new(Bucket::new(q))),
+            }),
+        }
+    }
+
+    /// Wait until a request of `kind` is allowed.
+    pub async fn acquire(&self, kind: RequestKind) {
+        let bucket = match kind {
+            RequestKind::Write => &self.buckets.writes,
+            RequestKind::Read => &self.buckets.reads,
+            RequestKind::Upload => &self.buckets.uploads,
+        };
+        let bucket = match bucket {
+            Some(bucket) => bucket,
+            None => return,
+        };
+        loop {
+            let wait = bucket.lock().unwrap().take();
+            match wait {
+                Ok(()) => return,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
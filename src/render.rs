@@ -0,0 +1,733 @@
+//! Rendering page content into other formats.
+use crate::{Node, NodeElement, Page};
+use chrono::NaiveDate;
+
+/// Tags rendered as blocks, separated from their neighbours by a blank line in Markdown.
+const BLOCK_TAGS: &[&str] = &[
+    "aside",
+    "blockquote",
+    "figure",
+    "h3",
+    "h4",
+    "hr",
+    "iframe",
+    "ol",
+    "p",
+    "pre",
+    "ul",
+    "video",
+];
+/// Tags without content or closing tag in HTML.
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+/// Tags put on their own line when pretty-printing HTML.
+const HTML_LINE_TAGS: &[&str] = &[
+    "aside",
+    "blockquote",
+    "figcaption",
+    "figure",
+    "h3",
+    "h4",
+    "hr",
+    "iframe",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "ul",
+    "video",
+];
+
+fn children(element: &NodeElement) -> &[Node] {
+    element.children.as_deref().unwrap_or_default()
+}
+
+fn attr<'a>(element: &'a NodeElement, name: &str) -> &'a str {
+    element
+        .attrs
+        .as_ref()
+        .and_then(|attrs| attrs.get(name))
+        .and_then(Option::as_deref)
+        .unwrap_or("")
+}
+
+fn is_block(node: &Node) -> bool {
+    matches!(node, Node::NodeElement(element) if BLOCK_TAGS.contains(&element.tag.as_str()))
+}
+
+/// Concatenated text of `nodes`.
+fn text_of(nodes: &[Node], text: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::NodeElement(element) => text_of(children(element), text),
+        }
+    }
+}
+
+fn escape_html(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Write `nodes` as HTML on a single line.
+fn html(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => escape_html(text, out),
+            Node::NodeElement(element) => {
+                open_tag(element, out);
+                if !VOID_TAGS.contains(&element.tag.as_str()) {
+                    html(children(element), out);
+                    out.push_str(&format!("</{}>", element.tag));
+                }
+            }
+        }
+    }
+}
+
+/// Write the opening tag of `element`, with its attributes sorted by name.
+fn open_tag(element: &NodeElement, out: &mut String) {
+    out.push('<');
+    out.push_str(&element.tag);
+    let mut attrs: Vec<_> = element.attrs.iter().flatten().collect();
+    attrs.sort();
+    for (name, value) in attrs {
+        out.push(' ');
+        out.push_str(name);
+        if let Some(value) = value {
+            out.push_str("=\"");
+            escape_html(value, out);
+            out.push('"');
+        }
+    }
+    out.push('>');
+}
+
+/// Options of [`nodes_to_html_with`].
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, nodes_to_html_with, render::HtmlOptions};
+///
+/// let content = nodes![p().text("Hello")];
+/// let options = HtmlOptions::new()
+///     .pretty(true)
+///     .document("Title")
+///     .author("Author", Some("https://t.me/author"));
+/// let html = nodes_to_html_with(&content, &options);
+/// assert!(html.starts_with("<!DOCTYPE html>"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HtmlOptions {
+    pretty: bool,
+    title: Option<String>,
+    author_name: Option<String>,
+    author_url: Option<String>,
+    date: Option<NaiveDate>,
+}
+
+impl HtmlOptions {
+    /// Compact content only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A standalone document with the title and author of `page`.
+    pub fn page(page: &Page) -> Self {
+        let mut options = Self::new().document(&page.title);
+        options.author_name = page.author_name.clone();
+        options.author_url = page.author_url.clone();
+        options
+    }
+
+    /// Put every block on its own line, indenting nested blocks.
+    ///
+    /// The content of `pre` is always kept as is.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Wrap the content in a standalone HTML document titled `title`,
+    /// laid out like a Telegraph article.
+    pub fn document(mut self, title: &str) -> Self {
+        self.title = Some(title.to_owned());
+        self
+    }
+
+    /// Author shown below the title of the document.
+    pub fn author(mut self, name: &str, url: Option<&str>) -> Self {
+        self.author_name = Some(name.to_owned());
+        self.author_url = url.map(str::to_owned);
+        self
+    }
+
+    /// Publication date shown below the title of the document.
+    pub fn date(mut self, date: NaiveDate) -> Self {
+        self.date = Some(date);
+        self
+    }
+}
+
+/// Render `nodes` as compact HTML, the inverse of [`html_to_nodes`](crate::html_to_nodes).
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, nodes_to_html};
+///
+/// let content = nodes![p().text("1 < 2 & ").child(a("/a?b=1&c=\"").text("link"))];
+/// assert_eq!(
+///     nodes_to_html(&content),
+///     r#"<p>1 &lt; 2 &amp; <a href="/a?b=1&amp;c=&quot;">link</a></p>"#
+/// );
+/// ```
+pub fn nodes_to_html(nodes: &[Node]) -> String {
+    nodes_to_html_with(nodes, &HtmlOptions::default())
+}
+
+/// Render `nodes` as HTML according to `options`.
+pub fn nodes_to_html_with(nodes: &[Node], options: &HtmlOptions) -> String {
+    let mut content = String::new();
+    let title = match &options.title {
+        Some(title) => title,
+        None if options.pretty => {
+            pretty_html(nodes, 0, &mut content);
+            return content;
+        }
+        None => {
+            html(nodes, &mut content);
+            return content;
+        }
+    };
+
+    let mut out =
+        String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>");
+    escape_html(title, &mut out);
+    out.push_str("</title>\n</head>\n<body>\n<div class=\"tl_page\">\n");
+    out.push_str("<header class=\"tl_article_header\">\n<h1>");
+    escape_html(title, &mut out);
+    out.push_str("</h1>\n<address>");
+    if let Some(author_name) = &options.author_name {
+        match &options.author_url {
+            Some(author_url) => {
+                out.push_str("<a rel=\"author\" href=\"");
+                escape_html(author_url, &mut out);
+                out.push_str("\">");
+                escape_html(author_name, &mut out);
+                out.push_str("</a>");
+            }
+            None => escape_html(author_name, &mut out),
+        }
+    }
+    if let Some(date) = options.date {
+        out.push_str(&format!(
+            "<time datetime=\"{}\">{}</time>",
+            date.format("%Y-%m-%d"),
+            date.format("%B %-d, %Y")
+        ));
+    }
+    out.push_str("</address>\n</header>\n<article class=\"tl_article_content\">");
+    if options.pretty {
+        out.push('\n');
+        pretty_html(nodes, 0, &mut out);
+    } else {
+        html(nodes, &mut out);
+    }
+    out.push_str("</article>\n</div>\n</body>\n</html>\n");
+    out
+}
+
+/// Write `nodes` as HTML with one block per line, indented by `depth` levels.
+fn pretty_html(nodes: &[Node], depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let mut inline_start = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        let element = match node {
+            Node::NodeElement(element) if HTML_LINE_TAGS.contains(&element.tag.as_str()) => element,
+            _ => continue,
+        };
+        pretty_inline(&nodes[inline_start..i], &indent, out);
+        inline_start = i + 1;
+
+        let has_lines = element.tag != "pre"
+            && children(element).iter().any(|child| {
+                matches!(child, Node::NodeElement(child) if HTML_LINE_TAGS.contains(&child.tag.as_str()))
+            });
+        if has_lines {
+            out.push_str(&indent);
+            open_tag(element, out);
+            out.push('\n');
+            pretty_html(children(element), depth + 1, out);
+            out.push_str(&format!("{}</{}>\n", indent, element.tag));
+        } else {
+            out.push_str(&indent);
+            html(std::slice::from_ref(node), out);
+            out.push('\n');
+        }
+    }
+    pretty_inline(&nodes[inline_start..], &indent, out);
+}
+
+/// Write a run of inline nodes on its own line, unless it is only whitespace.
+fn pretty_inline(nodes: &[Node], indent: &str, out: &mut String) {
+    let mut line = String::new();
+    html(nodes, &mut line);
+    if !line.trim().is_empty() {
+        out.push_str(indent);
+        out.push_str(line.trim());
+        out.push('\n');
+    }
+}
+
+/// Render `nodes` as CommonMark with GitHub extensions.
+///
+/// The output converts back into the same nodes with
+/// [`markdown_to_nodes`](crate::markdown_to_nodes) as far as Markdown allows:
+///
+/// - `h3` and `h4` become `##` and `###` headings
+/// - `pre` becomes a fenced code block
+/// - a `figure` with an image becomes `![caption](src)`
+/// - `aside`, `u`, `iframe`, `video` and figures of embeds are kept as raw HTML,
+///   which converts back with the `kuchiki` or `html` feature
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, nodes_to_markdown};
+///
+/// let content = nodes![h3().text("Title"), p().text("Some ").child(b().text("bold")).text(" text")];
+/// assert_eq!(nodes_to_markdown(&content), "## Title\n\nSome **bold** text\n");
+/// ```
+pub fn nodes_to_markdown(nodes: &[Node]) -> String {
+    let mut markdown = markdown_blocks(nodes).join("\n\n");
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Render `nodes` as Markdown blocks, wrapping runs of inline nodes in paragraphs.
+fn markdown_blocks(nodes: &[Node]) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::NodeElement(element) = node {
+            if is_block(node) {
+                blocks.extend(markdown_paragraph(&nodes[start..i]));
+                let block = markdown_block(element);
+                if !block.is_empty() {
+                    blocks.push(block);
+                }
+                start = i + 1;
+            }
+        }
+    }
+    blocks.extend(markdown_paragraph(&nodes[start..]));
+    blocks
+}
+
+fn markdown_paragraph(nodes: &[Node]) -> Option<String> {
+    let mut text = String::new();
+    markdown_inline(nodes, &mut text);
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let lines: Vec<_> = text.split('\n').map(escape_line_start).collect();
+    Some(lines.join("\n"))
+}
+
+fn markdown_block(element: &NodeElement) -> String {
+    match element.tag.as_str() {
+        "h3" | "h4" => {
+            let mut text = String::new();
+            markdown_inline(children(element), &mut text);
+            // Headings end at the first line break.
+            let text = text.replace("\\\n", " ");
+            let marker = if element.tag == "h3" { "##" } else { "###" };
+            format!("{} {}", marker, text.trim())
+        }
+        "blockquote" => prefix_lines(&markdown_blocks(children(element)).join("\n\n"), "> ", ">"),
+        "pre" => {
+            let mut code = String::new();
+            text_of(children(element), &mut code);
+            let code = code.strip_suffix('\n').unwrap_or(&code);
+            let fence = "`".repeat(longest_run(code, '`').max(2) + 1);
+            format!("{}\n{}\n{}", fence, code, fence)
+        }
+        "ul" | "ol" => {
+            let mut items = vec![];
+            for item in children(element) {
+                let nodes = match item {
+                    Node::NodeElement(li) if li.tag == "li" => children(li),
+                    node => std::slice::from_ref(node),
+                };
+                let marker = if element.tag == "ul" {
+                    "- ".to_owned()
+                } else {
+                    format!("{}. ", items.len() + 1)
+                };
+                // Items holding paragraphs come from loose lists, keep them loose.
+                let loose = nodes
+                    .iter()
+                    .any(|node| matches!(node, Node::NodeElement(p) if p.tag == "p"));
+                let body = markdown_blocks(nodes).join(if loose { "\n\n" } else { "\n" });
+                let indent = " ".repeat(marker.len());
+                let body = prefix_lines(&body, &indent, "");
+                items.push(format!(
+                    "{}{}",
+                    marker,
+                    &body[indent.len().min(body.len())..]
+                ));
+            }
+            items.join("\n")
+        }
+        "hr" => "---".to_owned(),
+        "figure" => {
+            let nodes = children(element);
+            let image = nodes.iter().find_map(|node| match node {
+                Node::NodeElement(img) if img.tag == "img" => Some(img),
+                _ => None,
+            });
+            match image {
+                Some(img) => {
+                    let mut caption = String::new();
+                    for node in nodes {
+                        if let Node::NodeElement(figcaption) = node {
+                            if figcaption.tag == "figcaption" {
+                                text_of(children(figcaption), &mut caption);
+                            }
+                        }
+                    }
+                    format!(
+                        "![{}]({})",
+                        escape_markdown(caption.trim()),
+                        markdown_url(attr(img, "src"))
+                    )
+                }
+                None => raw_html(element),
+            }
+        }
+        "p" => markdown_paragraph(children(element)).unwrap_or_default(),
+        _ => raw_html(element),
+    }
+}
+
+fn markdown_inline(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        let element = match node {
+            Node::Text(text) => {
+                out.push_str(&escape_markdown(text));
+                continue;
+            }
+            Node::NodeElement(element) => element,
+        };
+        let mut inner = String::new();
+        match element.tag.as_str() {
+            "b" | "strong" => emphasis(element, "**", out),
+            "i" | "em" => emphasis(element, "*", out),
+            "s" => emphasis(element, "~~", out),
+            "code" => {
+                text_of(children(element), &mut inner);
+                let ticks = "`".repeat(longest_run(&inner, '`') + 1);
+                let pad = if inner.starts_with('`') || inner.ends_with('`') {
+                    " "
+                } else {
+                    ""
+                };
+                out.push_str(&format!("{}{}{}{}{}", ticks, pad, inner, pad, ticks));
+            }
+            "a" => {
+                markdown_inline(children(element), &mut inner);
+                out.push_str(&format!(
+                    "[{}]({})",
+                    inner,
+                    markdown_url(attr(element, "href"))
+                ));
+            }
+            "img" => out.push_str(&format!("![]({})", markdown_url(attr(element, "src")))),
+            "br" => out.push_str("\\\n"),
+            "u" | "iframe" | "video" => out.push_str(&raw_html(element)),
+            _ => markdown_inline(children(element), out),
+        }
+    }
+}
+
+/// Wrap the content of `element` in `marker`, keeping surrounding whitespace outside of it.
+fn emphasis(element: &NodeElement, marker: &str, out: &mut String) {
+    let mut inner = String::new();
+    markdown_inline(children(element), &mut inner);
+    let trimmed = inner.trim();
+    if trimmed.is_empty() {
+        out.push_str(&inner);
+        return;
+    }
+    let start = inner.len() - inner.trim_start().len();
+    let end = inner.trim_end().len();
+    out.push_str(&inner[..start]);
+    out.push_str(marker);
+    out.push_str(trimmed);
+    out.push_str(marker);
+    out.push_str(&inner[end..]);
+}
+
+fn raw_html(element: &NodeElement) -> String {
+    let mut out = String::new();
+    html(
+        std::slice::from_ref(&Node::NodeElement(element.clone())),
+        &mut out,
+    );
+    out
+}
+
+fn markdown_url(url: &str) -> String {
+    if url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '<' || c == '>') {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_owned()
+    }
+}
+
+/// Escape the characters with a meaning in inline Markdown, and line breaks.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~' | '|' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape what would start a block, such as a heading or a list item, at the start of `line`.
+fn escape_line_start(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let digits = trimmed.len()
+        - trimmed
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if trimmed.starts_with(['#', '-', '+', '=']) {
+        format!("\\{}", trimmed)
+    } else if digits > 0 && trimmed[digits..].starts_with(['.', ')']) {
+        format!("{}\\{}", &trimmed[..digits], &trimmed[digits..])
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+/// Render `rows` as lines of columns separated by `|`, with a line under the first `head_rows` rows.
+pub(crate) fn render_table(rows: &[Vec<String>], head_rows: usize) -> String {
+    let mut widths = vec![];
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut lines = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        if i == head_rows && i > 0 {
+            let rule: Vec<_> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            lines.push(rule.join("-|-"));
+        }
+        let cells: Vec<_> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, width)| {
+                let cell = row.get(i).map(String::as_str).unwrap_or("");
+                format!("{}{}", cell, " ".repeat(width - cell.chars().count()))
+            })
+            .collect();
+        lines.push(cells.join(" | ").trim_end().to_owned());
+    }
+    lines.join("\n")
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|x| x != c).map(str::len).max().unwrap_or(0)
+}
+
+/// Prefix every line of `text` with `prefix`, or `empty_prefix` for empty lines.
+fn prefix_lines(text: &str, prefix: &str, empty_prefix: &str) -> String {
+    let lines: Vec<_> = text
+        .split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                empty_prefix.to_owned()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Options of [`nodes_to_text_with`].
+#[derive(Debug, Clone, Default)]
+pub struct TextOptions {
+    link_targets: bool,
+}
+
+impl TextOptions {
+    /// Text of links only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow the text of links with their target in parentheses, e.g. `docs (https://docs.rs)`.
+    ///
+    /// Links whose text is their target are left alone.
+    pub fn link_targets(mut self, link_targets: bool) -> Self {
+        self.link_targets = link_targets;
+        self
+    }
+}
+
+/// Extract the readable text of `nodes`.
+///
+/// Blocks are separated by blank lines, `br` becomes a line break and list items
+/// start with a bullet or their number. Media without caption are skipped.
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, nodes_to_text};
+///
+/// let content = nodes![h3().text("Title"), ol().item("one").item("two")];
+/// assert_eq!(nodes_to_text(&content), "Title\n\n1. one\n2. two");
+/// ```
+pub fn nodes_to_text(nodes: &[Node]) -> String {
+    nodes_to_text_with(nodes, &TextOptions::default())
+}
+
+/// Extract the readable text of `nodes` according to `options`.
+pub fn nodes_to_text_with(nodes: &[Node], options: &TextOptions) -> String {
+    text_blocks(nodes, options).join("\n\n")
+}
+
+/// Summary of `nodes` on a single line of at most `max_chars` characters,
+/// cut at a word boundary and ending with `…` if truncated.
+///
+/// Similar to the `description` Telegraph generates for a [`Page`].
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, nodes_to_summary};
+///
+/// let content = nodes![h3().text("Title"), p().text("A long paragraph of text.")];
+/// assert_eq!(nodes_to_summary(&content, 20), "Title A long…");
+/// ```
+pub fn nodes_to_summary(nodes: &[Node], max_chars: usize) -> String {
+    let text = nodes_to_text(nodes);
+    let words: Vec<_> = text.split_whitespace().collect();
+    let text = words.join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    // Drop the last word unless it ends right at the cut.
+    let cut = match cut.rfind(' ') {
+        Some(space) if !text[cut.len()..].starts_with(' ') => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Text of `nodes` as blocks, wrapping runs of inline nodes in paragraphs.
+fn text_blocks(nodes: &[Node], options: &TextOptions) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        let element = match node {
+            Node::NodeElement(element) if is_block(node) => element,
+            _ => continue,
+        };
+        let mut text = String::new();
+        text_inline(&nodes[start..i], options, &mut text);
+        push_block(&mut blocks, &text);
+        start = i + 1;
+
+        match element.tag.as_str() {
+            "ul" | "ol" => {
+                let mut items = vec![];
+                for item in children(element) {
+                    let nodes = match item {
+                        Node::NodeElement(li) if li.tag == "li" => children(li),
+                        node => std::slice::from_ref(node),
+                    };
+                    let marker = if element.tag == "ul" {
+                        "• ".to_owned()
+                    } else {
+                        format!("{}. ", items.len() + 1)
+                    };
+                    let body = text_blocks(nodes, options).join("\n");
+                    let indent = " ".repeat(marker.chars().count());
+                    let body = prefix_lines(&body, &indent, "");
+                    items.push(format!(
+                        "{}{}",
+                        marker,
+                        &body[indent.len().min(body.len())..]
+                    ));
+                }
+                push_block(&mut blocks, &items.join("\n"));
+            }
+            "pre" => {
+                let mut text = String::new();
+                text_of(children(element), &mut text);
+                push_block(&mut blocks, &text);
+            }
+            "p" | "h3" | "h4" => {
+                let mut text = String::new();
+                text_inline(children(element), options, &mut text);
+                push_block(&mut blocks, &text);
+            }
+            _ => {
+                for block in text_blocks(children(element), options) {
+                    push_block(&mut blocks, &block);
+                }
+            }
+        }
+    }
+    let mut text = String::new();
+    text_inline(&nodes[start..], options, &mut text);
+    push_block(&mut blocks, &text);
+    blocks
+}
+
+/// Append `block` unless it is blank, without its leading and trailing line breaks.
+fn push_block(blocks: &mut Vec<String>, block: &str) {
+    let block = block.trim_matches('\n');
+    if !block.trim().is_empty() {
+        blocks.push(block.to_owned());
+    }
+}
+
+fn text_inline(nodes: &[Node], options: &TextOptions, out: &mut String) {
+    for node in nodes {
+        let element = match node {
+            Node::Text(text) => {
+                out.push_str(text);
+                continue;
+            }
+            Node::NodeElement(element) => element,
+        };
+        match element.tag.as_str() {
+            "br" => out.push('\n'),
+            "a" if options.link_targets => {
+                let mut text = String::new();
+                text_inline(children(element), options, &mut text);
+                let href = attr(element, "href");
+                out.push_str(&text);
+                if !href.is_empty() && text.trim() != href {
+                    out.push_str(&format!(" ({})", href));
+                }
+            }
+            _ => text_inline(children(element), options, out),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Retrying failed requests with exponential backoff.
+use crate::{ApiErrorKind, Error, Result};
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+
+/// A retry that is about to happen, passed to [`RetryPolicy::on_retry`].
+#[derive(Debug)]
+pub struct RetryEvent<'a> {
+    /// API method being retried, e.g. `createPage` or `upload`.
+    pub method: &'static str,
+    /// Number of the attempt that failed, starting from 1.
+    pub attempt: u32,
+    /// Time to wait before the next attempt.
+    pub delay: Duration,
+    /// Error of the failed attempt.
+    pub error: &'a Error,
+}
+
+type RetryHook = Arc<dyn Fn(&RetryEvent<'_>) + Send + Sync>;
+
+/// Methods that change the account or its pages each time they are applied.
+pub const NON_IDEMPOTENT_METHODS: &[&str] = &[
+    "createAccount",
+    "createPage",
+    "editPage",
+    "revokeAccessToken",
+];
+
+/// Policy for retrying requests that failed with a retryable error.
+///
+/// See [`Error::is_retryable`] for which errors are retried. The methods in
+/// [`NON_IDEMPOTENT_METHODS`] are only retried when the server can't have applied them,
+/// see [`retry_non_idempotent`](Self::retry_non_idempotent).
+///
+/// ```
+/// use std::time::Duration;
+/// use telegraph_rs::RetryPolicy;
+///
+/// let policy = RetryPolicy::new()
+///     .max_attempts(5)
+///     .base_delay(Duration::from_millis(200))
+///     .on_retry(|event| eprintln!("retrying {}: {}", event.method, event.error));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    max_flood_wait: Option<Duration>,
+    retry_non_idempotent: bool,
+    on_retry: Option<RetryHook>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            max_flood_wait: Some(Duration::from_secs(60)),
+            retry_non_idempotent: false,
+            on_retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .field("max_flood_wait", &self.max_flood_wait)
+            .field("retry_non_idempotent", &self.retry_non_idempotent)
+            .field("on_retry", &self.on_retry.is_some())
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// Up to 3 attempts, starting with a 500ms delay doubled after each attempt, capped at 30s.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Delay before the second attempt, doubled for every following attempt.
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Upper bound of the backoff delay.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Randomize each backoff delay between half and all of its value, enabled by default.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Give up instead of waiting when the server asks to wait longer than this.
+    ///
+    /// `None` always waits as long as `FLOOD_WAIT_N` says, 60s by default.
+    pub fn max_flood_wait(mut self, max_flood_wait: Option<Duration>) -> Self {
+        self.max_flood_wait = max_flood_wait;
+        self
+    }
+
+    /// Also retry [`NON_IDEMPOTENT_METHODS`] after errors that the server may have
+    /// applied them despite, see [`Error::may_have_been_applied`].
+    ///
+    /// A retried createPage can then publish the same page twice. Disabled by default,
+    /// these methods are only retried after connection failures and flood control.
+    pub fn retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// Whether a `method` request that failed with `error` may be sent again.
+    pub fn allows(&self, method: &str, error: &Error) -> bool {
+        self.retry_non_idempotent
+            || !NON_IDEMPOTENT_METHODS.contains(&method)
+            || !error.may_have_been_applied()
+    }
+
+    /// Called before sleeping for each retry.
+    pub fn on_retry<F>(mut self, on_retry: F) -> Self
+    where
+        F: Fn(&RetryEvent<'_>) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Arc::new(on_retry));
+        self
+    }
+
+    /// Delay before retrying after `attempt` failed with `error`, or `None` to give up.
+    pub fn delay(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts || !error.is_retryable() {
+            return None;
+        }
+        if let Some(ApiErrorKind::FloodWait(seconds)) = error.api_kind() {
+            let wait = Duration::from_secs(*seconds);
+            return match self.max_flood_wait {
+                Some(max) if wait > max => None,
+                _ => Some(wait),
+            };
+        }
+
+        let exponent = (attempt - 1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        if self.jitter {
+            Some(delay.mul_f64(0.5 + fastrand::f64() / 2.0))
+        } else {
+            Some(delay)
+        }
+    }
+}
+
+/// Run `f` until it succeeds, fails with an error not worth retrying, or `policy` gives up.
+pub(crate) async fn execute<T, F, Fut>(
+    policy: Option<&RetryPolicy>,
+    method: &'static str,
+    mut f: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match f().await {
+            Ok(v) => return Ok(v),
+            Err(error) => error,
+        };
+        let policy = match policy {
+            Some(policy) => policy,
+            None => return Err(error),
+        };
+        let delay = match policy.delay(attempt, &error) {
+            Some(delay) if policy.allows(method, &error) => delay,
+            _ => return Err(error),
+        };
+        if let Some(on_retry) = &policy.on_retry {
+            on_retry(&RetryEvent {
+                method,
+                attempt,
+                delay,
+                error: &error,
+            });
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
@@ -0,0 +1,424 @@
+//! Normalizing arbitrary markup into content accepted by Telegraph.
+use crate::{
+    convert::{Conversion, Dropped},
+    render::render_table,
+    validate::{can_contain, ALLOWED_TAGS, BLOCK_TAGS},
+    Node, NodeElement,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Tags renamed by default.
+const RENAMES: &[(&str, &str)] = &[
+    ("article", "p"),
+    ("div", "p"),
+    ("footer", "p"),
+    ("header", "p"),
+    ("main", "p"),
+    ("section", "p"),
+    ("h1", "h3"),
+    ("h2", "h3"),
+    ("h5", "h4"),
+    ("h6", "h4"),
+    ("cite", "i"),
+    ("del", "s"),
+    ("dfn", "i"),
+    ("ins", "u"),
+    ("kbd", "code"),
+    ("samp", "code"),
+    ("strike", "s"),
+    ("tt", "code"),
+    ("var", "i"),
+];
+
+/// Tags dropped with their content by default.
+const DROPPED: &[&str] = &[
+    "button", "canvas", "embed", "head", "input", "link", "meta", "noscript", "object", "script",
+    "select", "style", "svg", "template", "textarea", "title",
+];
+
+/// How [`SanitizePolicy::sanitize`] maps markup onto Telegraph tags.
+///
+/// By default:
+///
+/// - `div`, `section` and other generic blocks become `p`
+/// - `h1`/`h2` become `h3`, `h5`/`h6` become `h4`
+/// - `table` becomes a `pre` with aligned columns
+/// - `script`, `style` and other non-content tags are dropped with their content
+/// - any other tag not accepted by Telegraph, such as `span`, is unwrapped into its children
+/// - so is an element that can't be nested in its parent, such as a `div` in an `a` or a `p`
+/// - attributes other than `href` and `src` are removed
+/// - URLs with a scheme other than `http`, `https`, `mailto` and `tg` are removed
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, sanitize::SanitizePolicy, NodeElement};
+///
+/// let div = NodeElement {
+///     tag: "div".to_owned(),
+///     attrs: None,
+///     children: Some(nodes![a("javascript:alert(1)").text("Click")]),
+/// };
+/// assert_eq!(SanitizePolicy::new().sanitize(&nodes![div]), nodes![p().text("Click")]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SanitizePolicy {
+    renames: HashMap<String, String>,
+    dropped: HashSet<String>,
+    tables_to_pre: bool,
+    url_schemes: HashSet<String>,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        SanitizePolicy {
+            renames: RENAMES
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            dropped: DROPPED.iter().map(|tag| tag.to_string()).collect(),
+            tables_to_pre: true,
+            url_schemes: ["http", "https", "mailto", "tg"]
+                .iter()
+                .map(|scheme| scheme.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// The default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn `from` elements into `to` elements.
+    pub fn rename(mut self, from: &str, to: &str) -> Self {
+        let from = from.to_ascii_lowercase();
+        self.dropped.remove(&from);
+        self.renames.insert(from, to.to_ascii_lowercase());
+        self
+    }
+
+    /// Remove `tag` elements with their content.
+    pub fn drop_tag(mut self, tag: &str) -> Self {
+        let tag = tag.to_ascii_lowercase();
+        self.renames.remove(&tag);
+        self.dropped.insert(tag);
+        self
+    }
+
+    /// Replace `tag` elements with their children.
+    pub fn unwrap_tag(mut self, tag: &str) -> Self {
+        let tag = tag.to_ascii_lowercase();
+        self.renames.remove(&tag);
+        self.dropped.remove(&tag);
+        self
+    }
+
+    /// Turn tables into `pre` blocks with aligned columns, enabled by default.
+    ///
+    /// When disabled, tables are unwrapped like other unsupported tags.
+    pub fn tables_to_pre(mut self, tables_to_pre: bool) -> Self {
+        self.tables_to_pre = tables_to_pre;
+        self
+    }
+
+    /// Also accept URLs with `scheme`, e.g. `data`.
+    ///
+    /// Relative URLs are always accepted.
+    pub fn allow_scheme(mut self, scheme: &str) -> Self {
+        self.url_schemes.insert(scheme.to_ascii_lowercase());
+        self
+    }
+
+    /// Whether `url` is relative or has an allowed scheme.
+    pub fn is_url_allowed(&self, url: &str) -> bool {
+        // Browsers ignore whitespace and control characters, as in `java\tscript:`.
+        let url: String = url
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect();
+        let scheme = match url.find([':', '/', '?', '#']) {
+            Some(end) if url[end..].starts_with(':') => &url[..end],
+            _ => return true,
+        };
+        self.url_schemes.contains(&scheme.to_ascii_lowercase())
+    }
+
+    /// Map `nodes` onto tags and attributes accepted by Telegraph.
+    pub fn sanitize(&self, nodes: &[Node]) -> Vec<Node> {
+        self.sanitize_report(nodes).nodes
+    }
+
+    /// Like [`sanitize`](Self::sanitize), also reporting everything that was dropped.
+    pub fn sanitize_report(&self, nodes: &[Node]) -> Conversion {
+        let mut conversion = Conversion::default();
+        self.sanitize_into(nodes, None, &mut conversion.nodes, &mut conversion.dropped);
+        conversion
+    }
+
+    /// Sanitize `nodes` into `out`, `parent` being the tag of the element holding them.
+    fn sanitize_into(
+        &self,
+        nodes: &[Node],
+        parent: Option<&str>,
+        out: &mut Vec<Node>,
+        dropped: &mut Vec<Dropped>,
+    ) {
+        for node in nodes {
+            self.sanitize_node(node, parent, out, dropped);
+        }
+    }
+
+    fn sanitize_node(
+        &self,
+        node: &Node,
+        parent: Option<&str>,
+        out: &mut Vec<Node>,
+        dropped: &mut Vec<Dropped>,
+    ) {
+        let element = match node {
+            Node::Text(text) => {
+                push(out, Node::Text(text.clone()));
+                return;
+            }
+            Node::NodeElement(element) => element,
+        };
+        let tag = element.tag.to_ascii_lowercase();
+        if self.dropped.contains(&tag) {
+            dropped.push(Dropped::Element { tag });
+            return;
+        }
+        if tag == "table" && self.tables_to_pre {
+            let text = table_text(element);
+            if can_contain(parent, "pre") {
+                out.push(Node::NodeElement(NodeElement {
+                    tag: "pre".to_owned(),
+                    attrs: None,
+                    children: Some(vec![Node::Text(text)]),
+                }));
+            } else {
+                // Where a `pre` can't go, keep the rows as lines of text.
+                dropped.push(Dropped::Tag { tag });
+                for (i, line) in text.lines().enumerate() {
+                    if i > 0 {
+                        out.push(Node::NodeElement(NodeElement {
+                            tag: "br".to_owned(),
+                            attrs: None,
+                            children: None,
+                        }));
+                    }
+                    push(out, Node::Text(line.to_owned()));
+                }
+            }
+            return;
+        }
+
+        let renamed = self.renames.get(&tag).cloned();
+        let kept = renamed.as_ref().unwrap_or(&tag);
+        let kept = ALLOWED_TAGS.contains(&kept.as_str()) && can_contain(parent, kept);
+        // A `div` renamed to `p` may hold paragraphs, which are then kept beside it.
+        let children_parent = if !kept || renamed.as_deref() == Some("p") {
+            parent
+        } else {
+            Some(renamed.as_deref().unwrap_or(&tag))
+        };
+        let mut children = vec![];
+        let element_children = element.children.as_deref().unwrap_or_default();
+        self.sanitize_into(element_children, children_parent, &mut children, dropped);
+        let tag = renamed.unwrap_or(tag);
+        if !kept {
+            dropped.push(Dropped::Tag { tag });
+            children.into_iter().for_each(|child| push(out, child));
+            return;
+        }
+
+        let url_attr = match tag.as_str() {
+            "a" => Some("href"),
+            "iframe" | "img" | "video" => Some("src"),
+            _ => None,
+        };
+        let mut url = None;
+        let mut attrs: Vec<_> = element.attrs.iter().flatten().collect();
+        attrs.sort();
+        for (name, value) in attrs {
+            let value = value.as_deref().map(str::trim);
+            match value {
+                Some(value)
+                    if url.is_none()
+                        && url_attr.is_some_and(|url_attr| name.eq_ignore_ascii_case(url_attr))
+                        && !value.is_empty()
+                        && self.is_url_allowed(value) =>
+                {
+                    url = Some(value);
+                }
+                _ => dropped.push(Dropped::Attribute {
+                    tag: tag.clone(),
+                    attribute: name.to_owned(),
+                    value: value.map(str::to_owned),
+                }),
+            }
+        }
+        let attrs = match (url_attr, url) {
+            (Some(name), Some(url)) => {
+                let mut attrs = HashMap::new();
+                attrs.insert(name.to_owned(), Some(url.to_owned()));
+                Some(attrs)
+            }
+            // Keep the text of links without a usable target.
+            (Some(_), None) if tag == "a" => {
+                dropped.push(Dropped::Tag { tag });
+                children.into_iter().for_each(|child| push(out, child));
+                return;
+            }
+            // Drop media without a source.
+            (Some(_), None) => {
+                dropped.push(Dropped::Element { tag });
+                return;
+            }
+            (None, _) => None,
+        };
+        // A `div` holding paragraphs cannot become a paragraph itself.
+        if tag == "p" && children.iter().any(is_block) {
+            wrap_inline_runs(children, out);
+            return;
+        }
+        push(
+            out,
+            Node::NodeElement(NodeElement {
+                tag,
+                attrs,
+                children: if children.is_empty() {
+                    None
+                } else {
+                    Some(children)
+                },
+            }),
+        );
+    }
+}
+
+/// Append `node` to `out`, merging adjacent text.
+fn push(out: &mut Vec<Node>, node: Node) {
+    match (out.last_mut(), node) {
+        (Some(Node::Text(last)), Node::Text(text)) => last.push_str(&text),
+        (_, node) => out.push(node),
+    }
+}
+
+/// Whether `node` is a block, an element renamed to `p` is unwrapped instead if it contains one.
+fn is_block(node: &Node) -> bool {
+    matches!(node, Node::NodeElement(element) if BLOCK_TAGS.contains(&element.tag.as_str()))
+}
+
+/// Append `nodes`, wrapping each run of inline nodes in a `p`.
+fn wrap_inline_runs(nodes: Vec<Node>, out: &mut Vec<Node>) {
+    let mut inline = vec![];
+    for node in nodes {
+        if is_block(&node) {
+            push_paragraph(std::mem::take(&mut inline), out);
+            out.push(node);
+        } else {
+            inline.push(node);
+        }
+    }
+    push_paragraph(inline, out);
+}
+
+/// Append a `p` of `children`, unless they are only whitespace.
+fn push_paragraph(children: Vec<Node>, out: &mut Vec<Node>) {
+    let blank = children
+        .iter()
+        .all(|node| matches!(node, Node::Text(text) if text.trim().is_empty()));
+    if !blank {
+        out.push(Node::NodeElement(NodeElement {
+            tag: "p".to_owned(),
+            attrs: None,
+            children: Some(children),
+        }));
+    }
+}
+
+/// Text of `nodes` with whitespace collapsed.
+fn cell_text(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::NodeElement(element) => {
+                text.push(' ');
+                text.push_str(&cell_text(element.children.as_deref().unwrap_or_default()));
+            }
+        }
+    }
+    let words: Vec<_> = text.split_whitespace().collect();
+    words.join(" ")
+}
+
+/// Render a `table` element as text with aligned columns.
+fn table_text(table: &NodeElement) -> String {
+    let mut rows = vec![];
+    table_rows(table, false, &mut rows);
+    // The header is made of the rows in `thead`, or else of the leading rows of `th` cells.
+    let head_rows = rows
+        .iter()
+        .take_while(|(in_head, cells)| {
+            *in_head || !cells.is_empty() && cells.iter().all(|cell| cell.tag == "th")
+        })
+        .count();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|(_, cells)| {
+            cells
+                .iter()
+                .map(|cell| cell_text(cell.children.as_deref().unwrap_or_default()))
+                .collect()
+        })
+        .collect();
+    render_table(&rows, head_rows)
+}
+
+/// Collect the cells of every row of `element`, in order, with whether they are in a `thead`.
+fn table_rows<'a>(
+    element: &'a NodeElement,
+    in_head: bool,
+    rows: &mut Vec<(bool, Vec<&'a NodeElement>)>,
+) {
+    for child in element.children.iter().flatten() {
+        let child = match child {
+            Node::NodeElement(child) => child,
+            Node::Text(_) => continue,
+        };
+        match child.tag.as_str() {
+            "thead" => table_rows(child, true, rows),
+            "tbody" | "tfoot" => table_rows(child, in_head, rows),
+            "tr" => {
+                let cells = child
+                    .children
+                    .iter()
+                    .flatten()
+                    .filter_map(|cell| match cell {
+                        Node::NodeElement(cell) if cell.tag == "td" || cell.tag == "th" => {
+                            Some(cell)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                rows.push((in_head, cells));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse `html` and sanitize it according to `policy`.
+///
+/// ```
+/// use telegraph_rs::{sanitize::{sanitize_html, SanitizePolicy}, builder::*, nodes};
+///
+/// let nodes = sanitize_html("<h1>Title</h1><script>alert(1)</script>", &SanitizePolicy::new());
+/// assert_eq!(nodes, nodes![h3().text("Title")]);
+/// ```
+#[cfg(any(feature = "kuchiki", feature = "html"))]
+pub fn sanitize_html(html: &str, policy: &SanitizePolicy) -> Vec<Node> {
+    policy.sanitize(&crate::html_to_nodes(html))
+}
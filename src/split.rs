@@ -0,0 +1,117 @@
+//! Splitting content too big for a single page.
+use crate::{Node, NodeElement};
+use std::mem;
+
+/// Serialized size of `node`.
+fn node_size(node: &Node) -> usize {
+    serde_json::to_string(node).unwrap().len()
+}
+
+/// Length of `text` once escaped in a JSON string.
+fn escaped_len(text: &str) -> usize {
+    serde_json::to_string(text).unwrap().len() - 2
+}
+
+/// Split `content` into parts whose serialized size is at most `max_size` bytes.
+///
+/// Parts are cut between top-level blocks. A block too big on its own is split
+/// between its children into several elements with the same tag, and text is cut
+/// at whitespace where possible. Always returns at least one part.
+///
+/// An element without children, such as a huge `img` URL, is kept whole even if
+/// it exceeds `max_size`. The pieces of an `ol` each number their items from 1.
+///
+/// ```
+/// use telegraph_rs::{builder::*, nodes, split::split_content};
+///
+/// let content = nodes![p().text("one"), p().text("two"), p().text("three")];
+/// let parts = split_content(&content, 64);
+/// assert_eq!(parts.len(), 2);
+/// ```
+pub fn split_content(content: &[Node], max_size: usize) -> Vec<Vec<Node>> {
+    // Brackets of the array.
+    let max_size = max_size.saturating_sub(2);
+    let mut parts = vec![];
+    let mut part = vec![];
+    let mut size = 0;
+    for node in content {
+        for piece in split_node(node.clone(), max_size) {
+            // Comma separating it from the previous node.
+            let piece_size = node_size(&piece) + 1;
+            if !part.is_empty() && size + piece_size > max_size {
+                parts.push(mem::take(&mut part));
+                size = 0;
+            }
+            size += piece_size;
+            part.push(piece);
+        }
+    }
+    if !part.is_empty() || parts.is_empty() {
+        parts.push(part);
+    }
+    parts
+}
+
+/// Split `node` into nodes of at most `max_size` bytes each.
+fn split_node(node: Node, max_size: usize) -> Vec<Node> {
+    if node_size(&node) <= max_size {
+        return vec![node];
+    }
+    match node {
+        Node::Text(text) => split_text(&text, max_size)
+            .into_iter()
+            .map(Node::Text)
+            .collect(),
+        Node::NodeElement(NodeElement {
+            tag,
+            attrs,
+            children: Some(children),
+        }) => {
+            let shell = NodeElement {
+                tag,
+                attrs,
+                children: Some(vec![]),
+            };
+            let children_size = max_size.saturating_sub(node_size(&shell.clone().into()));
+            split_content(&children, children_size)
+                .into_iter()
+                .map(|children| {
+                    Node::NodeElement(NodeElement {
+                        children: Some(children),
+                        ..shell.clone()
+                    })
+                })
+                .collect()
+        }
+        node => vec![node],
+    }
+}
+
+/// Cut `text` into strings of at most `max_size` bytes once serialized, preferably after whitespace.
+fn split_text(text: &str, max_size: usize) -> Vec<String> {
+    // Quotes of the string.
+    let max_size = max_size.saturating_sub(2).max(8);
+    let mut pieces = vec![];
+    let mut start = 0;
+    let mut size = 0;
+    let mut last_space = None;
+    let mut buf = [0; 4];
+    for (i, c) in text.char_indices() {
+        let c_size = escaped_len(c.encode_utf8(&mut buf));
+        if size + c_size > max_size && i > start {
+            let end = last_space.filter(|end| *end > start).unwrap_or(i);
+            pieces.push(text[start..end].to_owned());
+            size = escaped_len(&text[end..i]);
+            start = end;
+            last_space = None;
+        }
+        size += c_size;
+        if c.is_whitespace() {
+            last_space = Some(i + c.len_utf8());
+        }
+    }
+    if start < text.len() {
+        pieces.push(text[start..].to_owned());
+    }
+    pieces
+}
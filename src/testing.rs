@@ -0,0 +1,705 @@
+//! In-process fake Telegraph server for integration tests.
+//!
+//! [`FakeTelegraph`] speaks the same HTTP API as `api.telegra.ph` and `telegra.ph/upload`,
+//! keeps every account, page and upload in memory, and can be scripted to fail.
+//!
+//! ```
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use telegraph_rs::testing::{FakeTelegraph, Fault};
+//!
+//! let server = FakeTelegraph::start().await?;
+//! let telegraph = server.account("tester").create().await?;
+//!
+//! server.inject_for("createPage", Fault::FloodWait(3));
+//! let content = telegraph_rs::nodes!["Hello, world!"];
+//! assert!(telegraph.create_page("title", &content, false).await.is_err());
+//! # Ok(())
+//! # }
+//! ```
+use crate::{AccountBuilder, Node, MAX_CONTENT_SIZE, MAX_UPLOAD_SIZE};
+use hyper::{
+    body::Bytes,
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    io,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// A failure to be returned by the fake server instead of the regular response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Respond with `{"ok": false, "error": ...}`.
+    ApiError(String),
+    /// Respond with the given HTTP status code and an empty body.
+    Status(u16),
+    /// Respond with a `FLOOD_WAIT_{seconds}` API error.
+    FloodWait(u64),
+    /// Wait before handling the request normally.
+    Delay(Duration),
+}
+
+/// A file received by the fake `/upload` endpoint.
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+    /// Name of the file in the multipart form.
+    pub file_name: Option<String>,
+    /// Content type of the file in the multipart form.
+    pub mime: Option<String>,
+    /// Content of the file.
+    pub bytes: Bytes,
+    /// Path the file is served from, as returned to the uploader.
+    pub src: String,
+}
+
+#[derive(Debug, Clone)]
+struct AccountRecord {
+    short_name: String,
+    author_name: String,
+    author_url: String,
+}
+
+#[derive(Debug, Clone)]
+struct PageRecord {
+    path: String,
+    owner: String,
+    title: String,
+    author_name: String,
+    author_url: String,
+    content: Vec<Node>,
+    views: i32,
+    /// Views recorded at a (year, month, day, hour).
+    timed_views: Vec<((i32, u32, u32, u32), i32)>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    accounts: HashMap<String, AccountRecord>,
+    /// Pages in creation order.
+    pages: Vec<PageRecord>,
+    uploads: Vec<UploadedFile>,
+    faults: VecDeque<(Option<String>, Fault)>,
+    requests: Vec<String>,
+    counter: u64,
+}
+
+impl State {
+    fn next_id(&mut self) -> u64 {
+        self.counter += 1;
+        self.counter
+    }
+
+    fn take_fault(&mut self, method: &str) -> Option<Fault> {
+        let index = self
+            .faults
+            .iter()
+            .position(|(m, _)| m.is_none() || m.as_deref() == Some(method))?;
+        self.faults.remove(index).map(|(_, fault)| fault)
+    }
+}
+
+/// A fake Telegraph server listening on a random local port.
+///
+/// The server shuts down when this value is dropped.
+#[derive(Debug)]
+pub struct FakeTelegraph {
+    url: String,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl FakeTelegraph {
+    /// Start a new server on `127.0.0.1` with empty state.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub async fn start() -> io::Result<FakeTelegraph> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))?;
+        listener.set_nonblocking(true)?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State::default()));
+        let (tx, rx) = oneshot::channel::<()>();
+
+        let service_state = state.clone();
+        let service_url = url.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            let url = service_url.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle(state.clone(), url.clone(), request)
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener)
+            .map_err(io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                rx.await.ok();
+            });
+        tokio::spawn(server);
+
+        Ok(FakeTelegraph {
+            url,
+            state,
+            shutdown: Some(tx),
+        })
+    }
+
+    /// Base URL of the server, used both as API and upload URL.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// An [`AccountBuilder`] pointed at this server.
+    pub fn account(&self, short_name: &str) -> AccountBuilder {
+        AccountBuilder::new(short_name)
+            .api_url(&self.url)
+            .upload_url(&self.url)
+    }
+
+    /// Fail the next request, whatever its method.
+    pub fn inject(&self, fault: Fault) {
+        self.state.lock().unwrap().faults.push_back((None, fault));
+    }
+
+    /// Fail the next request to `method`, e.g. `createPage` or `upload`.
+    ///
+    /// Faults are consumed in the order they were injected.
+    pub fn inject_for(&self, method: &str, fault: Fault) {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .push_back((Some(method.to_owned()), fault));
+    }
+
+    /// Remove every pending fault.
+    pub fn clear_faults(&self) {
+        self.state.lock().unwrap().faults.clear();
+    }
+
+    /// Methods of all requests received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Files received by the upload endpoint so far.
+    pub fn uploads(&self) -> Vec<UploadedFile> {
+        self.state.lock().unwrap().uploads.clone()
+    }
+
+    /// Add `views` to the view counter of the page at `path`.
+    ///
+    /// These views only count towards the total, see [`add_views_at`](Self::add_views_at).
+    pub fn add_views(&self, path: &str, views: i32) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(page) = state.pages.iter_mut().find(|page| page.path == path) {
+            page.views += views;
+        }
+    }
+
+    /// Add `views` made at `(year, month, day, hour)` to the page at `path`.
+    pub fn add_views_at(&self, path: &str, time: (i32, u32, u32, u32), views: i32) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(page) = state.pages.iter_mut().find(|page| page.path == path) {
+            page.views += views;
+            page.timed_views.push((time, views));
+        }
+    }
+}
+
+impl Drop for FakeTelegraph {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+type ApiResponse = std::result::Result<Value, String>;
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    url: String,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let path = request.uri().path().trim_start_matches('/').to_owned();
+    let (method, arg) = match path.split_once('/') {
+        Some((method, arg)) => (method.to_owned(), Some(arg.to_owned())),
+        None => (path, None),
+    };
+
+    let fault = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(method.clone());
+        state.take_fault(&method)
+    };
+    match fault {
+        Some(Fault::Delay(duration)) => tokio::time::sleep(duration).await,
+        Some(Fault::Status(status)) => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() =
+                StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Ok(response);
+        }
+        Some(Fault::ApiError(error)) => return Ok(error_response(&method, error)),
+        Some(Fault::FloodWait(seconds)) => {
+            return Ok(error_response(&method, format!("FLOOD_WAIT_{}", seconds)))
+        }
+        None => {}
+    }
+
+    if method == "upload" {
+        return Ok(match upload(&state, request).await {
+            Ok(value) => json_response(value),
+            Err(error) => error_response(&method, error),
+        });
+    }
+    if method == "file" && request.method() == Method::GET {
+        return Ok(serve_file(
+            &state,
+            &format!("/file/{}", arg.unwrap_or_default()),
+        ));
+    }
+
+    let params = match params(request).await {
+        Ok(params) => params,
+        Err(error) => return Ok(error_response(&method, error)),
+    };
+    let mut state = state.lock().unwrap();
+    let result = match method.as_str() {
+        "createAccount" => create_account(&mut state, &url, &params),
+        "editAccountInfo" => edit_account_info(&mut state, &params),
+        "getAccountInfo" => get_account_info(&mut state, &url, &params),
+        "revokeAccessToken" => revoke_access_token(&mut state, &url, &params),
+        "createPage" => create_page(&mut state, &url, &params),
+        "editPage" => edit_page(&mut state, &url, &params, arg),
+        "getPage" => get_page(&state, &url, &params, arg),
+        "getPageList" => get_page_list(&state, &url, &params),
+        "getViews" => get_views(&state, &params, arg),
+        _ => Err("METHOD_NOT_FOUND".to_owned()),
+    };
+    Ok(match result {
+        Ok(value) => json_response(json!({ "ok": true, "result": value })),
+        Err(error) => error_response(&method, error),
+    })
+}
+
+fn json_response(value: Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(value.to_string()));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+fn error_response(method: &str, error: String) -> Response<Body> {
+    if method == "upload" {
+        json_response(json!({ "error": error }))
+    } else {
+        json_response(json!({ "ok": false, "error": error }))
+    }
+}
+
+/// Collect parameters from both the query string and an url-encoded body.
+async fn params(request: Request<Body>) -> Result<HashMap<String, String>, String> {
+    let mut params: HashMap<String, String> = request
+        .uri()
+        .query()
+        .map(|query| serde_urlencoded::from_str(query).unwrap_or_default())
+        .unwrap_or_default();
+    let is_form = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    if is_form {
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .map_err(|e| e.to_string())?;
+        let form: HashMap<String, String> =
+            serde_urlencoded::from_bytes(&body).map_err(|e| e.to_string())?;
+        params.extend(form);
+    }
+    Ok(params)
+}
+
+fn param<'a>(params: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    params.get(name).map(String::as_str)
+}
+
+fn authorize<'a>(
+    state: &'a mut State,
+    params: &HashMap<String, String>,
+) -> Result<(String, &'a mut AccountRecord), String> {
+    let token = param(params, "access_token").unwrap_or_default().to_owned();
+    match state.accounts.get_mut(&token) {
+        Some(account) => Ok((token, account)),
+        None => Err("ACCESS_TOKEN_INVALID".to_owned()),
+    }
+}
+
+fn account_json(account: &AccountRecord) -> Value {
+    json!({
+        "short_name": account.short_name,
+        "author_name": account.author_name,
+        "author_url": account.author_url,
+    })
+}
+
+fn create_account(state: &mut State, url: &str, params: &HashMap<String, String>) -> ApiResponse {
+    let short_name = match param(params, "short_name") {
+        Some(short_name) if !short_name.is_empty() => short_name.to_owned(),
+        _ => return Err("SHORT_NAME_REQUIRED".to_owned()),
+    };
+    let account = AccountRecord {
+        short_name,
+        author_name: param(params, "author_name").unwrap_or_default().to_owned(),
+        author_url: param(params, "author_url").unwrap_or_default().to_owned(),
+    };
+    let token = format!("fake-token-{}", state.next_id());
+    let mut value = account_json(&account);
+    value["access_token"] = json!(token);
+    value["auth_url"] = json!(format!("{}/auth/{}", url, state.next_id()));
+    state.accounts.insert(token, account);
+    Ok(value)
+}
+
+fn edit_account_info(state: &mut State, params: &HashMap<String, String>) -> ApiResponse {
+    let (_, account) = authorize(state, params)?;
+    if let Some(short_name) = param(params, "short_name") {
+        if short_name.is_empty() {
+            return Err("SHORT_NAME_REQUIRED".to_owned());
+        }
+        account.short_name = short_name.to_owned();
+    }
+    if let Some(author_name) = param(params, "author_name") {
+        account.author_name = author_name.to_owned();
+    }
+    if let Some(author_url) = param(params, "author_url") {
+        account.author_url = author_url.to_owned();
+    }
+    Ok(account_json(account))
+}
+
+fn get_account_info(state: &mut State, url: &str, params: &HashMap<String, String>) -> ApiResponse {
+    let auth_id = state.next_id();
+    let (token, account) = authorize(state, params)?;
+    let account = account.clone();
+    let fields: Vec<String> = match param(params, "fields") {
+        Some(fields) => {
+            serde_json::from_str(fields).map_err(|_| "FIELDS_FORMAT_INVALID".to_owned())?
+        }
+        None => vec![
+            "short_name".to_owned(),
+            "author_name".to_owned(),
+            "author_url".to_owned(),
+        ],
+    };
+    let page_count = state
+        .pages
+        .iter()
+        .filter(|page| page.owner == token)
+        .count();
+
+    let mut value = json!({});
+    for field in fields {
+        let field_value = match field.as_str() {
+            "short_name" => json!(account.short_name),
+            "author_name" => json!(account.author_name),
+            "author_url" => json!(account.author_url),
+            "auth_url" => json!(format!("{}/auth/{}", url, auth_id)),
+            "page_count" => json!(page_count),
+            _ => continue,
+        };
+        value[field] = field_value;
+    }
+    Ok(value)
+}
+
+fn revoke_access_token(
+    state: &mut State,
+    url: &str,
+    params: &HashMap<String, String>,
+) -> ApiResponse {
+    let (token, account) = authorize(state, params)?;
+    let account = account.clone();
+    let new_token = format!("fake-token-{}", state.next_id());
+    state.accounts.remove(&token);
+    state.accounts.insert(new_token.clone(), account);
+    for page in state.pages.iter_mut().filter(|page| page.owner == token) {
+        page.owner = new_token.clone();
+    }
+    Ok(json!({
+        "access_token": new_token,
+        "auth_url": format!("{}/auth/{}", url, state.next_id()),
+    }))
+}
+
+/// Check title and content of a page, returning the parsed content.
+fn page_content(params: &HashMap<String, String>) -> Result<(String, Vec<Node>), String> {
+    let title = match param(params, "title") {
+        Some(title) if !title.is_empty() => title.to_owned(),
+        _ => return Err("TITLE_REQUIRED".to_owned()),
+    };
+    let content = match param(params, "content") {
+        Some(content) if !content.is_empty() => content,
+        _ => return Err("CONTENT_REQUIRED".to_owned()),
+    };
+    if content.len() > MAX_CONTENT_SIZE {
+        return Err("CONTENT_TOO_BIG".to_owned());
+    }
+    let nodes: Vec<Node> =
+        serde_json::from_str(content).map_err(|_| "CONTENT_FORMAT_INVALID".to_owned())?;
+    if nodes.is_empty() {
+        return Err("CONTENT_TEXT_REQUIRED".to_owned());
+    }
+    Ok((title, nodes))
+}
+
+fn page_json(page: &PageRecord, url: &str, return_content: bool, token: Option<&str>) -> Value {
+    let mut description = String::new();
+    collect_text(&page.content, &mut description);
+    let description: String = description.chars().take(150).collect();
+
+    let mut value = json!({
+        "path": page.path,
+        "url": format!("{}/{}", url, page.path),
+        "title": page.title,
+        "description": description.trim(),
+        "author_name": page.author_name,
+        "author_url": page.author_url,
+        "views": page.views,
+    });
+    if return_content {
+        value["content"] = json!(page.content);
+    }
+    if let Some(token) = token {
+        value["can_edit"] = json!(page.owner == token);
+    }
+    value
+}
+
+fn collect_text(nodes: &[Node], text: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::NodeElement(element) => {
+                if let Some(children) = &element.children {
+                    collect_text(children, text);
+                }
+                text.push(' ');
+            }
+        }
+    }
+}
+
+fn is_true(params: &HashMap<String, String>, name: &str) -> bool {
+    param(params, name).is_some_and(|value| value == "true" || value == "1")
+}
+
+fn create_page(state: &mut State, url: &str, params: &HashMap<String, String>) -> ApiResponse {
+    let (token, account) = authorize(state, params)?;
+    let account = account.clone();
+    let (title, content) = page_content(params)?;
+
+    let slug: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let path = format!("{}-{}", slug.trim_matches('-'), state.next_id());
+    let page = PageRecord {
+        path,
+        owner: token.clone(),
+        title,
+        author_name: param(params, "author_name").map_or(account.author_name, str::to_owned),
+        author_url: param(params, "author_url").map_or(account.author_url, str::to_owned),
+        content,
+        views: 0,
+        timed_views: vec![],
+    };
+    let value = page_json(&page, url, is_true(params, "return_content"), Some(&token));
+    state.pages.push(page);
+    Ok(value)
+}
+
+fn edit_page(
+    state: &mut State,
+    url: &str,
+    params: &HashMap<String, String>,
+    path: Option<String>,
+) -> ApiResponse {
+    let (token, _) = authorize(state, params)?;
+    let path = match path.as_deref().or_else(|| param(params, "path")) {
+        Some(path) if !path.is_empty() => path.to_owned(),
+        _ => return Err("PAGE_NOT_FOUND".to_owned()),
+    };
+    let (title, content) = page_content(params)?;
+    let page = state
+        .pages
+        .iter_mut()
+        .find(|page| page.path == path)
+        .ok_or_else(|| "PAGE_NOT_FOUND".to_owned())?;
+    if page.owner != token {
+        return Err("PAGE_ACCESS_DENIED".to_owned());
+    }
+    page.title = title;
+    page.content = content;
+    if let Some(author_name) = param(params, "author_name") {
+        page.author_name = author_name.to_owned();
+    }
+    if let Some(author_url) = param(params, "author_url") {
+        page.author_url = author_url.to_owned();
+    }
+    Ok(page_json(
+        page,
+        url,
+        is_true(params, "return_content"),
+        Some(&token),
+    ))
+}
+
+fn find_page<'a>(state: &'a State, path: Option<&str>) -> Result<&'a PageRecord, String> {
+    state
+        .pages
+        .iter()
+        .find(|page| Some(page.path.as_str()) == path)
+        .ok_or_else(|| "PAGE_NOT_FOUND".to_owned())
+}
+
+fn get_page(
+    state: &State,
+    url: &str,
+    params: &HashMap<String, String>,
+    path: Option<String>,
+) -> ApiResponse {
+    let path = path.as_deref().or_else(|| param(params, "path"));
+    let page = find_page(state, path)?;
+    Ok(page_json(
+        page,
+        url,
+        is_true(params, "return_content"),
+        param(params, "access_token"),
+    ))
+}
+
+fn get_page_list(state: &State, url: &str, params: &HashMap<String, String>) -> ApiResponse {
+    let token = param(params, "access_token").unwrap_or_default();
+    if !state.accounts.contains_key(token) {
+        return Err("ACCESS_TOKEN_INVALID".to_owned());
+    }
+    let offset: usize = param(params, "offset")
+        .map_or(Ok(0), str::parse)
+        .map_err(|_| "OFFSET_INVALID".to_owned())?;
+    let limit: usize = param(params, "limit")
+        .map_or(Ok(50), str::parse)
+        .map_err(|_| "LIMIT_INVALID".to_owned())?;
+    if limit > 200 {
+        return Err("LIMIT_INVALID".to_owned());
+    }
+
+    let owned: Vec<_> = state
+        .pages
+        .iter()
+        .rev()
+        .filter(|page| page.owner == token)
+        .collect();
+    let pages: Vec<_> = owned
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|page| page_json(page, url, false, Some(token)))
+        .collect();
+    Ok(json!({ "total_count": owned.len(), "pages": pages }))
+}
+
+fn get_views(state: &State, params: &HashMap<String, String>, path: Option<String>) -> ApiResponse {
+    let path = path.as_deref().or_else(|| param(params, "path"));
+    let page = find_page(state, path)?;
+    let parse = |name| param(params, name).and_then(|value| value.parse::<i64>().ok());
+    let filter = [parse("year"), parse("month"), parse("day"), parse("hour")];
+    if filter[0].is_none() {
+        return Ok(json!({ "views": page.views }));
+    }
+    let views: i32 = page
+        .timed_views
+        .iter()
+        .filter(|((year, month, day, hour), _)| {
+            let time = [*year as i64, *month as i64, *day as i64, *hour as i64];
+            filter
+                .iter()
+                .zip(&time)
+                .all(|(expected, actual)| expected.is_none() || *expected == Some(*actual))
+        })
+        .map(|(_, views)| views)
+        .sum();
+    Ok(json!({ "views": views }))
+}
+
+async fn upload(state: &Arc<Mutex<State>>, request: Request<Body>) -> ApiResponse {
+    let boundary = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| multer::parse_boundary(value).ok())
+        .ok_or_else(|| "Invalid request".to_owned())?;
+    let mut multipart = multer::Multipart::new(request.into_body(), boundary);
+
+    let mut files = vec![];
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        let file_name = field.file_name().map(str::to_owned);
+        let mime = field.content_type().map(|mime| mime.to_string());
+        let bytes = field.bytes().await.map_err(|e| e.to_string())?;
+        let extension = match mime.as_deref() {
+            Some("image/jpeg") | Some("image/jpg") => "jpg",
+            Some("image/png") => "png",
+            Some("image/gif") => "gif",
+            Some("video/mp4") => "mp4",
+            _ => return Err("File type invalid".to_owned()),
+        };
+        if bytes.len() as u64 > MAX_UPLOAD_SIZE {
+            return Err("File too big".to_owned());
+        }
+        files.push((file_name, mime, bytes, extension));
+    }
+
+    let mut state = state.lock().unwrap();
+    let mut sources = vec![];
+    for (file_name, mime, bytes, extension) in files {
+        let src = format!("/file/{:016x}.{}", state.next_id(), extension);
+        sources.push(json!({ "src": src }));
+        state.uploads.push(UploadedFile {
+            file_name,
+            mime,
+            bytes,
+            src,
+        });
+    }
+    Ok(Value::Array(sources))
+}
+
+fn serve_file(state: &Arc<Mutex<State>>, src: &str) -> Response<Body> {
+    let state = state.lock().unwrap();
+    match state.uploads.iter().find(|file| file.src == src) {
+        Some(file) => {
+            let mut response = Response::new(Body::from(file.bytes.clone()));
+            if let Some(mime) = file.mime.as_deref().and_then(|mime| mime.parse().ok()) {
+                response.headers_mut().insert(CONTENT_TYPE, mime);
+            }
+            response
+        }
+        None => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            response
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Converting images into files accepted by the upload endpoint.
+//!
+//! Formats the endpoint doesn't accept (webp, bmp) are converted to png if they have an
+//! alpha channel and to jpeg otherwise. Images still too big are recompressed, then
+//! downscaled until they fit. Animated GIFs keep all their frames.
+//!
+//! Only the formats decoded by the `image` crate are converted: HEIC and AVIF are not
+//! supported, as decoding them needs system libraries, and are rejected with
+//! [`Error::UnsupportedFileType`].
+//!
+//! When uploading, files are converted once before the request is sent, on a blocking
+//! thread, with the [`ImageOptions`] set by
+//! [`AccountBuilder::image_options`](crate::AccountBuilder::image_options). Files bigger
+//! than [`MAX_INPUT_SIZE`] aren't decoded.
+use crate::{
+    upload::{sniff_mime, Probe, MAX_UPLOAD_SIZE},
+    Error, Result, UploadBytes,
+};
+use image::{
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType as PngFilter, PngEncoder},
+    },
+    imageops::{self, FilterType},
+    metadata::{LoopCount, Orientation},
+    AnimationDecoder, DynamicImage, Frame, ImageDecoder, ImageReader,
+};
+use std::{convert::TryFrom, io::Cursor};
+
+/// Size of the largest file converted by default, in bytes.
+pub const MAX_INPUT_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Smallest width or height an image is downscaled to.
+const MIN_DIMENSION: u32 = 16;
+
+/// How images are fitted into the upload limits.
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    pub(crate) max_size: u64,
+    pub(crate) max_input_size: u64,
+    pub(crate) max_dimension: Option<u32>,
+    pub(crate) quality: u8,
+    pub(crate) min_quality: u8,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            max_size: MAX_UPLOAD_SIZE,
+            max_input_size: MAX_INPUT_SIZE,
+            max_dimension: None,
+            quality: 90,
+            min_quality: 60,
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size the file must fit in, [`MAX_UPLOAD_SIZE`] by default.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Reject files bigger than `max_input_size` bytes instead of decoding them,
+    /// [`MAX_INPUT_SIZE`] by default.
+    pub fn max_input_size(mut self, max_input_size: u64) -> Self {
+        self.max_input_size = max_input_size;
+        self
+    }
+
+    /// Downscale images whose width or height exceeds `max_dimension` pixels.
+    pub fn max_dimension(mut self, max_dimension: u32) -> Self {
+        self.max_dimension = Some(max_dimension.max(MIN_DIMENSION));
+        self
+    }
+
+    /// JPEG quality of converted images, 90 by default.
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = quality.clamp(1, 100);
+        self
+    }
+
+    /// Lowest JPEG quality tried before downscaling, 60 by default.
+    pub fn min_quality(mut self, min_quality: u8) -> Self {
+        self.min_quality = min_quality.clamp(1, 100);
+        self
+    }
+}
+
+/// Convert `file` into a jpeg, png, gif or mp4 file of at most `options.max_size` bytes.
+///
+/// A file already acceptable is returned as is, without being decoded. Otherwise its
+/// extension is replaced to match the new format, and its EXIF orientation is applied
+/// to the pixels as the converted file has no EXIF. Videos can't be converted.
+///
+/// ```
+/// use image::{DynamicImage, ImageFormat};
+/// use std::io::Cursor;
+/// use telegraph_rs::{transcode::{fit_image, ImageOptions}, UploadBytes};
+///
+/// let mut bmp = vec![];
+/// DynamicImage::new_rgb8(64, 64).write_to(&mut Cursor::new(&mut bmp), ImageFormat::Bmp)?;
+/// let file = fit_image(&UploadBytes::new(bmp, "chart.bmp", "image/bmp"), &ImageOptions::new())?;
+/// assert_eq!(file.file_name(), "chart.jpg");
+/// assert_eq!(file.mime(), "image/jpeg");
+/// # Ok::<(), telegraph_rs::Error>(())
+/// ```
+pub fn fit_image(file: &UploadBytes, options: &ImageOptions) -> Result<UploadBytes> {
+    let bytes = file.bytes();
+    let sniffed = sniff_mime(bytes);
+    if sniffed == Some("video/mp4") || sniffed.is_some() && fits(bytes, options) {
+        return Ok(file.clone());
+    }
+    if bytes.len() as u64 > options.max_input_size {
+        return Err(Error::FileTooBig {
+            size: bytes.len() as u64,
+            max_size: options.max_input_size,
+        });
+    }
+
+    let (bytes, mime, extension) = if sniffed == Some("image/gif") {
+        (fit_gif(bytes, options)?, "image/gif", "gif")
+    } else {
+        let mut decoder = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()?
+            .into_decoder()
+            .map_err(|_| Error::UnsupportedFileType)?;
+        // A broken EXIF block is no reason not to convert the image.
+        let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+        let mut image =
+            DynamicImage::from_decoder(decoder).map_err(|_| Error::UnsupportedFileType)?;
+        image.apply_orientation(orientation);
+        if image.color().has_alpha() {
+            (fit_png(image, options)?, "image/png", "png")
+        } else {
+            (fit_jpeg(image, options)?, "image/jpeg", "jpg")
+        }
+    };
+    Ok(UploadBytes::new(
+        bytes,
+        &with_extension(file.file_name(), extension),
+        mime,
+    ))
+}
+
+/// Whether a file starting with `head` can be decoded.
+pub(crate) fn can_convert(head: &[u8]) -> bool {
+    image::guess_format(head).is_ok_and(|format| format.reading_enabled())
+}
+
+/// Whether a file is converted with `options` before being uploaded.
+///
+/// Only images that can be decoded are converted, when they would be rejected or may
+/// exceed the [`max_dimension`](ImageOptions::max_dimension).
+pub(crate) fn needs_conversion(probe: &Probe, options: &ImageOptions) -> bool {
+    probe.head.as_deref().is_some_and(can_convert)
+        && (options.max_dimension.is_some() || probe.check(options.max_size).is_err())
+}
+
+/// Whether an accepted file is small enough.
+fn fits(bytes: &[u8], options: &ImageOptions) -> bool {
+    if bytes.len() as u64 > options.max_size {
+        return false;
+    }
+    match options.max_dimension {
+        Some(max) => ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .is_some_and(|(width, height)| width <= max && height <= max),
+        None => true,
+    }
+}
+
+/// Replace the extension of `file_name`.
+fn with_extension(file_name: &str, extension: &str) -> String {
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => file_name,
+    };
+    format!("{}.{}", stem, extension)
+}
+
+/// Scale of an image of `width`×`height` for it to fit in `max_dimension`.
+fn initial_scale(width: u32, height: u32, options: &ImageOptions) -> f64 {
+    let max = options.max_dimension.unwrap_or(u32::MAX) as f64;
+    (max / width.max(height) as f64).min(1.0)
+}
+
+/// Scale `(width, height)` by `scale`, or `None` if the result would be too small.
+fn scaled(width: u32, height: u32, scale: f64) -> Option<(u32, u32)> {
+    let (w, h) = (
+        (width as f64 * scale).round() as u32,
+        (height as f64 * scale).round() as u32,
+    );
+    if w.min(h) < MIN_DIMENSION.min(width.min(height)) {
+        None
+    } else {
+        Some((w.max(1), h.max(1)))
+    }
+}
+
+/// Downscale `image` until `encode` produces a file small enough.
+fn shrink(
+    image: &DynamicImage,
+    options: &ImageOptions,
+    mut encode: impl FnMut(&DynamicImage) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let (width, height) = (image.width(), image.height());
+    let mut scale = initial_scale(width, height, options);
+    let mut smallest = u64::MAX;
+    while let Some((w, h)) = scaled(width, height, scale) {
+        let resized = if (w, h) == (width, height) {
+            image.clone()
+        } else {
+            image.resize_exact(w, h, FilterType::Lanczos3)
+        };
+        let bytes = encode(&resized)?;
+        if bytes.len() as u64 <= options.max_size {
+            return Ok(bytes);
+        }
+        smallest = smallest.min(bytes.len() as u64);
+        scale *= 0.75;
+    }
+    Err(Error::FileTooBig {
+        size: smallest,
+        max_size: options.max_size,
+    })
+}
+
+fn fit_jpeg(image: DynamicImage, options: &ImageOptions) -> Result<Vec<u8>> {
+    let image = DynamicImage::ImageRgb8(image.to_rgb8());
+    let min_quality = options.min_quality.min(options.quality);
+    shrink(&image, options, |image| {
+        let mut quality = options.quality;
+        loop {
+            let mut bytes = vec![];
+            JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(image)?;
+            if bytes.len() as u64 <= options.max_size || quality == min_quality {
+                return Ok(bytes);
+            }
+            quality = quality.saturating_sub(10).max(min_quality);
+        }
+    })
+}
+
+fn fit_png(image: DynamicImage, options: &ImageOptions) -> Result<Vec<u8>> {
+    shrink(&image, options, |image| {
+        let mut bytes = vec![];
+        let encoder =
+            PngEncoder::new_with_quality(&mut bytes, CompressionType::Best, PngFilter::Adaptive);
+        image.write_with_encoder(encoder)?;
+        Ok(bytes)
+    })
+}
+
+/// Downscale every frame of a GIF until it fits, keeping its loop count.
+fn fit_gif(bytes: &[u8], options: &ImageOptions) -> Result<Vec<u8>> {
+    let decoder = GifDecoder::new(Cursor::new(bytes)).map_err(|_| Error::UnsupportedFileType)?;
+    // A GIF without loop extension is played once, `loop_count` tells it loops forever.
+    let repeat = has_loop_extension(bytes).then(|| match decoder.loop_count() {
+        LoopCount::Infinite => Repeat::Infinite,
+        LoopCount::Finite(n) => Repeat::Finite(u16::try_from(n.get()).unwrap_or(u16::MAX)),
+    });
+    let frames = decoder.into_frames().collect_frames()?;
+    let (width, height) = frames.iter().fold((1, 1), |(w, h), frame| {
+        let buffer = frame.buffer();
+        (
+            w.max(frame.left() + buffer.width()),
+            h.max(frame.top() + buffer.height()),
+        )
+    });
+    let mut scale = initial_scale(width, height, options);
+    let mut smallest = u64::MAX;
+    while scaled(width, height, scale).is_some() {
+        let resized = frames.iter().map(|frame| {
+            let buffer = frame.buffer();
+            let resize = |n: u32| ((n as f64 * scale).round() as u32).max(1);
+            Frame::from_parts(
+                imageops::resize(
+                    buffer,
+                    resize(buffer.width()),
+                    resize(buffer.height()),
+                    FilterType::Triangle,
+                ),
+                (frame.left() as f64 * scale).round() as u32,
+                (frame.top() as f64 * scale).round() as u32,
+                frame.delay(),
+            )
+        });
+        let mut bytes = vec![];
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            if let Some(repeat) = repeat {
+                encoder.set_repeat(repeat)?;
+            }
+            encoder.encode_frames(resized)?;
+        }
+        if bytes.len() as u64 <= options.max_size {
+            return Ok(bytes);
+        }
+        smallest = smallest.min(bytes.len() as u64);
+        scale *= 0.75;
+    }
+    Err(Error::FileTooBig {
+        size: smallest,
+        max_size: options.max_size,
+    })
+}
+
+/// Whether the GIF `bytes` have a NETSCAPE2.0 application extension, which sets how
+/// many times it is played.
+fn has_loop_extension(bytes: &[u8]) -> bool {
+    bytes
+        .windows(14)
+        .any(|window| window == b"\x21\xFF\x0BNETSCAPE2.0")
+}
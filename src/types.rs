@@ -1,7 +1,28 @@
-use super::{error::Error, utils::*};
+use super::error::Error;
+#[cfg(feature = "upload")]
+use super::utils::*;
+#[cfg(feature = "upload")]
+use crate::{
+    metadata::MetadataReport,
+    upload::{sniff_mime, Probe, UploadStream, SNIFF_LEN},
+};
+#[cfg(feature = "upload")]
+use bytes::Bytes;
+#[cfg(feature = "upload")]
+use futures_util::{
+    future::{self, BoxFuture},
+    stream,
+};
+#[cfg(feature = "upload")]
 use reqwest::multipart::Part;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{collections::HashMap, fmt};
+#[cfg(feature = "upload")]
+use std::{path::Path, sync::Mutex};
+#[cfg(feature = "upload")]
+use tokio::io::AsyncRead;
+#[cfg(feature = "upload")]
+use tokio_util::io::ReaderStream;
 
 /// This object represents a Telegraph account.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -28,6 +49,115 @@ pub struct Account {
     pub page_count: Option<i32>,
 }
 
+/// A field of [`Account`] that can be requested with getAccountInfo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountField {
+    ShortName,
+    AuthorName,
+    AuthorUrl,
+    AuthUrl,
+    PageCount,
+}
+
+impl AccountField {
+    /// Fields returned when none are requested.
+    pub const DEFAULT: [AccountField; 3] = [
+        AccountField::ShortName,
+        AccountField::AuthorName,
+        AccountField::AuthorUrl,
+    ];
+
+    /// Name of the field in the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountField::ShortName => "short_name",
+            AccountField::AuthorName => "author_name",
+            AccountField::AuthorUrl => "author_url",
+            AccountField::AuthUrl => "auth_url",
+            AccountField::PageCount => "page_count",
+        }
+    }
+}
+
+impl fmt::Display for AccountField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An [`Account`] returned by getAccountInfo, together with the fields that were requested.
+///
+/// Accessors return [`Error::FieldNotRequested`] for fields that were not requested.
+#[derive(Debug, Clone)]
+pub struct AccountInfo {
+    account: Account,
+    fields: Vec<AccountField>,
+}
+
+impl AccountInfo {
+    pub(crate) fn new(account: Account, fields: Vec<AccountField>) -> Self {
+        AccountInfo { account, fields }
+    }
+
+    /// Fields that were requested.
+    pub fn fields(&self) -> &[AccountField] {
+        &self.fields
+    }
+
+    /// Whether `field` was requested.
+    pub fn has(&self, field: AccountField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    fn field<'a, T>(&'a self, field: AccountField, value: &'a Option<T>) -> Result<&'a T, Error> {
+        if !self.has(field) {
+            return Err(Error::FieldNotRequested(field));
+        }
+        value.as_ref().ok_or(Error::MissingField(field))
+    }
+
+    /// Account name.
+    pub fn short_name(&self) -> Result<&str, Error> {
+        self.field(AccountField::ShortName, &self.account.short_name)
+            .map(String::as_str)
+    }
+
+    /// Default author name used when creating new articles.
+    pub fn author_name(&self) -> Result<&str, Error> {
+        self.field(AccountField::AuthorName, &self.account.author_name)
+            .map(String::as_str)
+    }
+
+    /// Default profile link, empty if not set.
+    pub fn author_url(&self) -> Result<&str, Error> {
+        self.field(AccountField::AuthorUrl, &self.account.author_url)
+            .map(String::as_str)
+    }
+
+    /// URL to authorize a browser on telegra.ph, valid for one use and for 5 minutes only.
+    pub fn auth_url(&self) -> Result<&str, Error> {
+        self.field(AccountField::AuthUrl, &self.account.auth_url)
+            .map(String::as_str)
+    }
+
+    /// Number of pages belonging to the account.
+    pub fn page_count(&self) -> Result<i32, Error> {
+        self.field(AccountField::PageCount, &self.account.page_count)
+            .copied()
+    }
+
+    /// The raw account object.
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+
+    /// Take the raw account object.
+    pub fn into_account(self) -> Account {
+        self.account
+    }
+}
+
 /// This object represents a list of Telegraph articles belonging to an account. Most recently created articles first.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PageList {
@@ -76,15 +206,33 @@ pub struct PageViews {
 /// This abstract object represents a DOM Node.
 ///
 /// It can be a String which represents a DOM text node or a NodeElement object.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Node {
     Text(String),
     NodeElement(NodeElement),
 }
 
+impl From<&str> for Node {
+    fn from(text: &str) -> Self {
+        Node::Text(text.to_owned())
+    }
+}
+
+impl From<String> for Node {
+    fn from(text: String) -> Self {
+        Node::Text(text)
+    }
+}
+
+impl From<NodeElement> for Node {
+    fn from(element: NodeElement) -> Self {
+        Node::NodeElement(element)
+    }
+}
+
 /// This object represents a DOM element node.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct NodeElement {
     /// Name of the DOM element.
     /// Available tags: a, aside, b, blockquote, br, code, em, figcaption, figure, h3, h4, hr, i, iframe, img, li, ol, p, pre, s, strong, u, ul, video.
@@ -113,13 +261,55 @@ pub enum UploadResult {
 pub struct ImageInfo {
     /// Path of the file uploaded.
     pub src: String,
+    /// Metadata removed from the file before uploading it, if enabled by
+    /// [`AccountBuilder::strip_metadata`](crate::AccountBuilder::strip_metadata).
+    #[cfg(feature = "upload")]
+    #[serde(skip)]
+    pub metadata: Option<MetadataReport>,
 }
 
+/// A file that can be uploaded.
+///
+/// Implemented by paths, [`UploadBytes`] and [`UploadReader`]. Content already in memory,
+/// such as [`Bytes`], is uploaded as an [`UploadBytes`], which gives it a file name and MIME
+/// type and converts from [`Bytes`] with defaults.
 #[cfg(feature = "upload")]
 pub trait Uploadable {
     fn part(&self) -> Result<Part, Error>;
+
+    /// Content of the file as a stream, sent instead of [`part`](Uploadable::part) when
+    /// available so that the bytes sent are reported to
+    /// [`AccountBuilder::on_upload_progress`](crate::AccountBuilder::on_upload_progress).
+    ///
+    /// `None` by default.
+    fn stream(&self) -> BoxFuture<'_, Result<Option<UploadStream>, Error>> {
+        Box::pin(future::ready(Ok(None)))
+    }
+
+    /// Size and leading bytes of the file, used to check it before sending it.
+    ///
+    /// Nothing is known by default.
+    fn probe(&self) -> BoxFuture<'_, Result<Probe, Error>> {
+        Box::pin(future::ready(Ok(Probe::default())))
+    }
+
+    /// The whole file, if it can be read without consuming it.
+    ///
+    /// Needed to strip metadata, `None` by default.
+    fn to_bytes(&self) -> BoxFuture<'_, Result<Option<UploadBytes>, Error>> {
+        Box::pin(future::ready(Ok(None)))
+    }
 }
 
+/// Files are streamed from disk with [`tokio::fs`], the MIME type is sniffed from their
+/// content or guessed from their extension.
+///
+/// [`part`](Uploadable::part) reads the whole file without yielding to the runtime, uploads
+/// use [`stream`](Uploadable::stream) instead.
+///
+/// With the `image-processing` feature, files rejected by [`Probe::check`] are read into
+/// memory and converted with [`transcode::fit_image`](crate::transcode::fit_image) when
+/// uploaded.
 #[cfg(feature = "upload")]
 impl<T> Uploadable for T
 where
@@ -127,10 +317,258 @@ where
 {
     fn part(&self) -> Result<Part, Error> {
         let path = self.as_ref();
-        let bytes = read_to_bytes(path)?;
-        let part = Part::bytes(bytes)
-            .file_name(path.file_name().unwrap().to_string_lossy().to_string())
-            .mime_str(&guess_mime(path))?;
-        Ok(part)
+        path_bytes(path, std::fs::read(path)?).part()
+    }
+
+    fn stream(&self) -> BoxFuture<'_, Result<Option<UploadStream>, Error>> {
+        let path = self.as_ref().to_owned();
+        Box::pin(async move { path_stream(&path).await.map(Some) })
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<Probe, Error>> {
+        let path = self.as_ref().to_owned();
+        Box::pin(async move { probe_path(&path).await })
+    }
+
+    fn to_bytes(&self) -> BoxFuture<'_, Result<Option<UploadBytes>, Error>> {
+        let path = self.as_ref().to_owned();
+        Box::pin(async move {
+            let bytes = tokio::fs::read(&path).await?;
+            Ok(Some(path_bytes(&path, bytes)))
+        })
+    }
+}
+
+/// The content `bytes` of the file at `path`.
+#[cfg(feature = "upload")]
+fn path_bytes(path: &Path, bytes: Vec<u8>) -> UploadBytes {
+    let mime = match sniff_mime(&bytes) {
+        Some(mime) => mime.to_owned(),
+        None => guess_mime(path),
+    };
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    UploadBytes::new(bytes, &file_name, &mime)
+}
+
+#[cfg(feature = "upload")]
+async fn path_stream(path: &Path) -> Result<UploadStream, Error> {
+    let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+    let probe = probe_path(path).await?;
+    let mime = match probe.head.as_deref().and_then(sniff_mime) {
+        Some(mime) => mime.to_owned(),
+        None => guess_mime(path),
+    };
+    let file = tokio::fs::File::open(path).await?;
+    let length = file.metadata().await?.len();
+    let stream = ReaderStream::new(file);
+    Ok(UploadStream::new(stream, Some(length), &file_name, &mime))
+}
+
+#[cfg(feature = "upload")]
+async fn probe_path(path: &Path) -> Result<Probe, Error> {
+    use tokio::io::AsyncReadExt;
+    let file = tokio::fs::File::open(path).await?;
+    let size = file.metadata().await?.len();
+    let mut head = vec![];
+    file.take(SNIFF_LEN as u64).read_to_end(&mut head).await?;
+    Ok(Probe {
+        size: Some(size),
+        head: Some(head),
+    })
+}
+
+/// Size of the chunks an [`UploadBytes`] is sent in, for its progress to be reported.
+#[cfg(feature = "upload")]
+const BYTES_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file held in memory.
+///
+/// With the `image-processing` feature, images are converted with
+/// [`transcode::fit_image`](crate::transcode::fit_image) when uploaded.
+///
+/// ```
+/// # async fn run(telegraph: telegraph_rs::Telegraph, png: Vec<u8>) -> Result<(), telegraph_rs::Error> {
+/// use telegraph_rs::UploadBytes;
+///
+/// let file = UploadBytes::new(png, "chart.png", "image/png");
+/// let images = telegraph.upload_media(&[file]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "upload")]
+#[derive(Debug, Clone)]
+pub struct UploadBytes {
+    bytes: Bytes,
+    file_name: String,
+    mime: String,
+}
+
+#[cfg(feature = "upload")]
+impl UploadBytes {
+    /// `bytes` can be a `Vec<u8>`, a `&'static [u8]` or a [`Bytes`], which is not copied.
+    pub fn new(bytes: impl Into<Bytes>, file_name: &str, mime: &str) -> Self {
+        UploadBytes {
+            bytes: bytes.into(),
+            file_name: file_name.to_owned(),
+            mime: mime.to_owned(),
+        }
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn mime(&self) -> &str {
+        &self.mime
+    }
+
+    /// Remove metadata from the file with [`strip_metadata`](crate::metadata::strip_metadata).
+    pub fn strip_metadata(&self) -> Result<(UploadBytes, MetadataReport), Error> {
+        let (bytes, report) = crate::metadata::strip_metadata(&self.bytes)?;
+        let file = UploadBytes {
+            bytes: bytes.into(),
+            ..self.clone()
+        };
+        Ok((file, report))
+    }
+
+    /// The file split into chunks of [`BYTES_CHUNK_SIZE`] bytes.
+    fn bytes_stream(&self) -> Result<UploadStream, Error> {
+        let bytes = self.bytes.clone();
+        let chunks: Vec<_> = (0..bytes.len())
+            .step_by(BYTES_CHUNK_SIZE)
+            .map(|start| Ok(bytes.slice(start..bytes.len().min(start + BYTES_CHUNK_SIZE))))
+            .collect();
+        Ok(UploadStream::new(
+            stream::iter(chunks),
+            Some(bytes.len() as u64),
+            &self.file_name,
+            &self.mime,
+        ))
+    }
+
+    fn probe_bytes(&self) -> Probe {
+        Probe {
+            size: Some(self.bytes.len() as u64),
+            head: Some(self.bytes[..self.bytes.len().min(SNIFF_LEN)].to_vec()),
+        }
+    }
+}
+
+#[cfg(feature = "upload")]
+impl Uploadable for UploadBytes {
+    fn part(&self) -> Result<Part, Error> {
+        self.bytes_stream()?.into_part()
+    }
+
+    fn stream(&self) -> BoxFuture<'_, Result<Option<UploadStream>, Error>> {
+        Box::pin(future::ready(self.bytes_stream().map(Some)))
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<Probe, Error>> {
+        Box::pin(future::ready(Ok(self.probe_bytes())))
+    }
+
+    fn to_bytes(&self) -> BoxFuture<'_, Result<Option<UploadBytes>, Error>> {
+        Box::pin(future::ready(Ok(Some(self.clone()))))
+    }
+}
+
+/// Content in memory, named `file` with the MIME type sniffed from the content, or
+/// `application/octet-stream` if unknown.
+///
+/// [`Bytes`] can't implement [`Uploadable`] itself, as it could implement `AsRef<Path>`.
+#[cfg(feature = "upload")]
+impl From<Bytes> for UploadBytes {
+    fn from(bytes: Bytes) -> Self {
+        let mime = sniff_mime(&bytes).unwrap_or("application/octet-stream");
+        UploadBytes::new(bytes, "file", mime)
+    }
+}
+
+/// A file read from an [`AsyncRead`] while the request is sent.
+///
+/// The reader can only be read once: uploading it again, including when a failed
+/// upload is retried, returns [`Error::ReaderConsumed`]. Buffer the content into an
+/// [`UploadBytes`] to upload it several times.
+///
+/// ```
+/// # async fn run(telegraph: telegraph_rs::Telegraph) -> Result<(), telegraph_rs::Error> {
+/// use telegraph_rs::UploadReader;
+///
+/// let file = tokio::fs::File::open("video.mp4").await?;
+/// let length = file.metadata().await?.len();
+/// let file = UploadReader::new(file, "video.mp4", "video/mp4").length(length);
+/// let videos = telegraph.upload_media(&[file]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "upload")]
+#[derive(Debug)]
+pub struct UploadReader<R> {
+    reader: Mutex<Option<R>>,
+    file_name: String,
+    mime: String,
+    length: Option<u64>,
+}
+
+#[cfg(feature = "upload")]
+impl<R> UploadReader<R>
+where
+    R: AsyncRead + Send + Sync + 'static,
+{
+    pub fn new(reader: R, file_name: &str, mime: &str) -> Self {
+        UploadReader {
+            reader: Mutex::new(Some(reader)),
+            file_name: file_name.to_owned(),
+            mime: mime.to_owned(),
+            length: None,
+        }
+    }
+
+    /// Size of the content, sent as the length of the part if known.
+    pub fn length(mut self, length: u64) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    fn reader_stream(&self) -> Result<UploadStream, Error> {
+        let reader = self
+            .reader
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(Error::ReaderConsumed)?;
+        Ok(UploadStream::new(
+            ReaderStream::new(reader),
+            self.length,
+            &self.file_name,
+            &self.mime,
+        ))
+    }
+}
+
+#[cfg(feature = "upload")]
+impl<R> Uploadable for UploadReader<R>
+where
+    R: AsyncRead + Send + Sync + 'static,
+{
+    fn part(&self) -> Result<Part, Error> {
+        self.reader_stream()?.into_part()
+    }
+
+    fn stream(&self) -> BoxFuture<'_, Result<Option<UploadStream>, Error>> {
+        Box::pin(future::ready(self.reader_stream().map(Some)))
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<Probe, Error>> {
+        Box::pin(future::ready(Ok(Probe {
+            size: self.length,
+            head: None,
+        })))
     }
 }
@@ -0,0 +1,356 @@
+//! Checks, options and progress of uploads.
+pub use crate::MAX_UPLOAD_SIZE;
+use crate::{Error, Result};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use reqwest::{multipart::Part, Body};
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// MIME types accepted by the upload endpoint.
+pub const UPLOAD_MIME_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "video/mp4"];
+/// Number of leading bytes needed by [`sniff_mime`].
+pub const SNIFF_LEN: usize = 12;
+
+/// Major brands of ISO media files that are mp4 videos.
+///
+/// Others, such as QuickTime (`qt  `), 3GPP or HEIF, are rejected by the upload endpoint.
+const MP4_BRANDS: &[&[u8]] = &[
+    b"avc1", b"dash", b"f4v ", b"iso2", b"iso3", b"iso4", b"iso5", b"iso6", b"isom", b"M4V ",
+    b"mmp4", b"mp41", b"mp42", b"MSNV",
+];
+
+/// MIME type of a file accepted by the upload endpoint, from its first bytes.
+///
+/// Returns `None` if `head` doesn't start like a jpeg, png, gif or mp4 file.
+///
+/// ```
+/// use telegraph_rs::upload::sniff_mime;
+///
+/// assert_eq!(sniff_mime(b"GIF89a\x01\x00"), Some("image/gif"));
+/// assert_eq!(sniff_mime(b"<svg></svg>"), None);
+/// ```
+pub fn sniff_mime(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"\xFF\xD8\xFF") {
+        Some("image/jpeg")
+    } else if head.starts_with(b"\x89PNG\r\n\x1A\n") {
+        Some("image/png")
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if head.get(4..8) == Some(b"ftyp") {
+        let brand = head.get(8..12)?;
+        MP4_BRANDS.contains(&brand).then_some("video/mp4")
+    } else {
+        None
+    }
+}
+
+/// What is known about a file before uploading it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Probe {
+    /// Size of the file in bytes, if known.
+    pub size: Option<u64>,
+    /// Up to [`SNIFF_LEN`] leading bytes of the file, if they can be read without consuming it.
+    pub head: Option<Vec<u8>>,
+}
+
+impl Probe {
+    /// Check the file against the limits of the upload endpoint.
+    ///
+    /// What is unknown is not checked.
+    pub fn check(&self, max_size: u64) -> Result<()> {
+        if let Some(size) = self.size.filter(|size| *size > max_size) {
+            return Err(Error::FileTooBig { size, max_size });
+        }
+        if self
+            .head
+            .as_ref()
+            .is_some_and(|head| sniff_mime(head).is_none())
+        {
+            return Err(Error::UnsupportedFileType);
+        }
+        Ok(())
+    }
+}
+
+/// Options of [`Telegraph::upload_files`](crate::Telegraph::upload_files).
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    pub(crate) max_size: u64,
+    pub(crate) chunk_size: usize,
+    pub(crate) concurrency: usize,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        UploadOptions {
+            max_size: MAX_UPLOAD_SIZE,
+            chunk_size: 4,
+            concurrency: 4,
+        }
+    }
+}
+
+impl UploadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject files bigger than `max_size` bytes without sending them, [`MAX_UPLOAD_SIZE`] by default.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Send at most `chunk_size` files per request, 4 by default.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Send at most `concurrency` requests at once, 4 by default.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+/// Content of a file streamed into an upload request.
+///
+/// Returned by [`Uploadable::stream`](crate::Uploadable::stream) so that the bytes sent can be reported to
+/// [`AccountBuilder::on_upload_progress`](crate::AccountBuilder::on_upload_progress).
+pub struct UploadStream {
+    stream: Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send + Sync>>,
+    length: Option<u64>,
+    file_name: String,
+    mime: String,
+}
+
+impl fmt::Debug for UploadStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadStream")
+            .field("length", &self.length)
+            .field("file_name", &self.file_name)
+            .field("mime", &self.mime)
+            .finish()
+    }
+}
+
+impl UploadStream {
+    /// `length` is the size of the file, if known.
+    pub fn new<S>(stream: S, length: Option<u64>, file_name: &str, mime: &str) -> Self
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        UploadStream {
+            stream: Box::pin(stream),
+            length,
+            file_name: file_name.to_owned(),
+            mime: mime.to_owned(),
+        }
+    }
+
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn mime(&self) -> &str {
+        &self.mime
+    }
+
+    /// Build the multipart part.
+    pub fn into_part(self) -> Result<Part> {
+        let body = Body::wrap_stream(self.stream);
+        let part = match self.length {
+            Some(length) => Part::stream_with_length(body, length),
+            None => Part::stream(body),
+        };
+        Ok(part.file_name(self.file_name).mime_str(&self.mime)?)
+    }
+}
+
+/// Bytes of a file sent so far, in an [`UploadEvent::Sent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// Request the file is sent in, as in [`UploadEvent::Started`].
+    pub request: u64,
+    /// Index of the file in the request.
+    pub file: usize,
+    pub file_name: String,
+    /// Bytes of this file sent so far.
+    pub sent: u64,
+    /// Size of this file, if known.
+    pub size: Option<u64>,
+    /// Bytes of all the files of the request sent so far.
+    pub total_sent: u64,
+    /// Size of all the files of the request, if known.
+    pub total_size: Option<u64>,
+}
+
+/// Progress of an upload request, passed to
+/// [`AccountBuilder::on_upload_progress`](crate::AccountBuilder::on_upload_progress).
+///
+/// Each request gets its own `request` number, telling apart those sent at once by
+/// [`Telegraph::upload_files`](crate::Telegraph::upload_files). A request retried after
+/// a failure starts again from [`UploadEvent::Started`] with the same number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadEvent {
+    /// The request is about to be sent.
+    Started {
+        request: u64,
+        files: usize,
+        /// Size of all the files, if known.
+        total_size: Option<u64>,
+    },
+    /// A piece of a file was handed to the connection.
+    ///
+    /// Files that don't implement [`Uploadable::stream`](crate::Uploadable::stream) aren't reported.
+    Sent(UploadProgress),
+    /// The server answered, `ok` is whether the files were uploaded.
+    Finished {
+        request: u64,
+        total_sent: u64,
+        ok: bool,
+    },
+}
+
+/// Callback given to [`AccountBuilder::on_upload_progress`](crate::AccountBuilder::on_upload_progress).
+#[derive(Clone)]
+pub(crate) struct ProgressHook(Arc<dyn Fn(&UploadEvent) + Send + Sync>);
+
+impl ProgressHook {
+    pub(crate) fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&UploadEvent) + Send + Sync + 'static,
+    {
+        ProgressHook(Arc::new(hook))
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// Number of the next request reported to a [`ProgressHook`].
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes sent by each file of a request.
+#[derive(Clone)]
+pub(crate) struct Tracker {
+    hook: ProgressHook,
+    request: u64,
+    files: Arc<Mutex<Vec<FileProgress>>>,
+}
+
+#[derive(Default)]
+struct FileProgress {
+    file_name: String,
+    sent: u64,
+    size: Option<u64>,
+}
+
+impl Tracker {
+    /// Track a new request of `files` files.
+    pub(crate) fn new(hook: ProgressHook, files: usize) -> Self {
+        Tracker {
+            hook,
+            request: NEXT_REQUEST.fetch_add(1, Ordering::Relaxed),
+            files: Arc::new(Mutex::new(
+                (0..files).map(|_| FileProgress::default()).collect(),
+            )),
+        }
+    }
+
+    /// Track the same request again, when it is retried.
+    pub(crate) fn restart(&self) -> Self {
+        let files = self.files.lock().unwrap().len();
+        Tracker {
+            hook: self.hook.clone(),
+            request: self.request,
+            files: Arc::new(Mutex::new(
+                (0..files).map(|_| FileProgress::default()).collect(),
+            )),
+        }
+    }
+
+    /// Report the bytes read from `stream` as those of file number `file`.
+    pub(crate) fn track(&self, file: usize, stream: UploadStream) -> UploadStream {
+        self.files.lock().unwrap()[file] = FileProgress {
+            file_name: stream.file_name.clone(),
+            sent: 0,
+            size: stream.length,
+        };
+        let tracker = self.clone();
+        let inner = stream.stream.map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                tracker.sent(file, bytes.len() as u64);
+            }
+            chunk
+        });
+        UploadStream {
+            stream: Box::pin(inner),
+            ..stream
+        }
+    }
+
+    pub(crate) fn start(&self) {
+        let (files, total_size) = {
+            let files = self.files.lock().unwrap();
+            (files.len(), total_size(&files))
+        };
+        (self.hook.0)(&UploadEvent::Started {
+            request: self.request,
+            files,
+            total_size,
+        });
+    }
+
+    fn sent(&self, file: usize, bytes: u64) {
+        let progress = {
+            let mut files = self.files.lock().unwrap();
+            files[file].sent += bytes;
+            UploadProgress {
+                request: self.request,
+                file,
+                file_name: files[file].file_name.clone(),
+                sent: files[file].sent,
+                size: files[file].size,
+                total_sent: files.iter().map(|file| file.sent).sum(),
+                total_size: total_size(&files),
+            }
+        };
+        (self.hook.0)(&UploadEvent::Sent(progress));
+    }
+
+    pub(crate) fn finish(&self, ok: bool) {
+        let total_sent = self
+            .files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|file| file.sent)
+            .sum();
+        (self.hook.0)(&UploadEvent::Finished {
+            request: self.request,
+            total_sent,
+            ok,
+        });
+    }
+}
+
+/// Size of all the files, unknown if that of a file is.
+fn total_size(files: &[FileProgress]) -> Option<u64> {
+    files.iter().map(|file| file.size).sum()
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "upload")]
 use std::path::Path;
 
 #[cfg(feature = "upload")]
@@ -20,22 +21,30 @@ pub fn read_to_bytes<P: AsRef<Path>>(path: P) -> crate::Result<Vec<u8>> {
     Ok(bytes)
 }
 
-/// Parse html to node string
+/// Parse html to nodes
 ///
 /// ```rust
-/// use telegraph_rs::html_to_node;
+/// use telegraph_rs::html_to_nodes;
 ///
-/// let node = html_to_node("<p>Hello, world</p>");
-/// assert_eq!(node, r#"[{"tag":"p","attrs":null,"children":["Hello, world"]}]"#);
+/// let nodes = html_to_nodes("<p>Hello, world</p>");
+/// assert_eq!(serde_json::to_string(&nodes).unwrap(), r#"[{"tag":"p","children":["Hello, world"]}]"#);
 /// ```
 #[cfg(feature = "html")]
-pub fn html_to_node(html: &str) -> String {
+pub fn html_to_nodes(html: &str) -> Vec<crate::Node> {
     use html_parser::Dom;
 
     let dom = Dom::parse(html).unwrap();
-    let nodes = dom.children
-        .into_iter()
-        .map(|node| crate::html_to_node_inner(&node))
-        .collect::<Vec<_>>();
-    serde_json::to_string(&nodes).unwrap()
+    let nodes: Vec<_> = dom
+        .children
+        .iter()
+        .filter_map(crate::html_to_node_inner)
+        .collect();
+    crate::convert::normalize_whitespace(&nodes, false)
+}
+
+/// Parse html to nodes, serialized as JSON.
+#[cfg(feature = "html")]
+#[deprecated(since = "0.7.0", note = "use `html_to_nodes`, which returns the nodes")]
+pub fn html_to_node(html: &str) -> String {
+    serde_json::to_string(&html_to_nodes(html)).unwrap()
 }
@@ -0,0 +1,259 @@
+//! Checking page content against the rules of the Telegraph API.
+//!
+//! ```
+//! use telegraph_rs::{builder::*, nodes, validate, ViolationKind};
+//!
+//! let content = nodes![p().child(h3().text("Heading in a paragraph"))];
+//! let violations = validate(&content);
+//! assert_eq!(violations[0].path, [0, 0]);
+//! assert!(matches!(violations[0].kind, ViolationKind::IllegalNesting { .. }));
+//! ```
+use crate::{Node, NodeElement};
+use std::fmt;
+
+/// Maximum size of the serialized content of a page, in bytes.
+pub const MAX_CONTENT_SIZE: usize = 64 * 1024;
+
+/// Tags accepted by Telegraph.
+pub const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "aside",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "em",
+    "figcaption",
+    "figure",
+    "h3",
+    "h4",
+    "hr",
+    "i",
+    "iframe",
+    "img",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "strong",
+    "u",
+    "ul",
+    "video",
+];
+
+/// Tags that cannot have children.
+const VOID_TAGS: &[&str] = &["br", "hr", "iframe", "img", "video"];
+/// Block tags, which cannot be nested in inline tags or in paragraphs.
+pub(crate) const BLOCK_TAGS: &[&str] = &[
+    "aside",
+    "blockquote",
+    "figure",
+    "h3",
+    "h4",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "ul",
+];
+/// Tags that may only contain inline content.
+const INLINE_CONTAINERS: &[&str] = &[
+    "a",
+    "b",
+    "code",
+    "em",
+    "i",
+    "s",
+    "strong",
+    "u",
+    "p",
+    "h3",
+    "h4",
+    "pre",
+    "figcaption",
+];
+
+/// What is wrong with a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The tag is not accepted by Telegraph.
+    DisallowedTag(String),
+    /// The attribute is not accepted on this tag.
+    DisallowedAttribute { tag: String, attribute: String },
+    /// `attribute` is required by `tag` but missing or empty.
+    EmptyAttribute { tag: String, attribute: String },
+    /// `child` cannot be placed inside `parent`, `None` meaning the top level.
+    IllegalNesting {
+        parent: Option<String>,
+        child: String,
+    },
+    /// The serialized content is `size` bytes, more than [`MAX_CONTENT_SIZE`].
+    ContentTooBig { size: usize },
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::DisallowedTag(tag) => write!(f, "tag <{}> is not allowed", tag),
+            ViolationKind::DisallowedAttribute { tag, attribute } => {
+                write!(f, "attribute {} is not allowed on <{}>", attribute, tag)
+            }
+            ViolationKind::EmptyAttribute { tag, attribute } => {
+                write!(f, "<{}> requires a non-empty {}", tag, attribute)
+            }
+            ViolationKind::IllegalNesting {
+                parent: Some(parent),
+                child,
+            } => write!(f, "<{}> cannot contain {}", parent, child),
+            ViolationKind::IllegalNesting {
+                parent: None,
+                child,
+            } => write!(f, "{} cannot be at the top level", child),
+            ViolationKind::ContentTooBig { size } => write!(
+                f,
+                "content is {} bytes, more than {} bytes",
+                size, MAX_CONTENT_SIZE
+            ),
+        }
+    }
+}
+
+/// A rule broken by page content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Indices of the offending node, from the top-level node down to it.
+    ///
+    /// Empty for violations about the whole content.
+    pub path: Vec<usize>,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            return write!(f, "{}", self.kind);
+        }
+        let path: Vec<String> = self.path.iter().map(usize::to_string).collect();
+        write!(f, "{} (node {})", self.kind, path.join("/"))
+    }
+}
+
+/// Check `content` against the rules of Telegraph, returning every rule broken.
+///
+/// Reports disallowed tags and attributes, empty `href` and `src`, illegal nesting
+/// and content bigger than [`MAX_CONTENT_SIZE`] once serialized.
+pub fn validate(content: &[Node]) -> Vec<Violation> {
+    let mut violations = vec![];
+    let mut path = vec![];
+    check_children(None, content, &mut path, &mut violations);
+
+    let size = serde_json::to_string(content).unwrap().len();
+    if size > MAX_CONTENT_SIZE {
+        violations.push(Violation {
+            path: vec![],
+            kind: ViolationKind::ContentTooBig { size },
+        });
+    }
+    violations
+}
+
+fn check_children(
+    parent: Option<&str>,
+    children: &[Node],
+    path: &mut Vec<usize>,
+    violations: &mut Vec<Violation>,
+) {
+    for (i, child) in children.iter().enumerate() {
+        path.push(i);
+        match child {
+            Node::Text(text) => {
+                if matches!(parent, Some("ul") | Some("ol")) && !text.trim().is_empty() {
+                    violations.push(Violation {
+                        path: path.clone(),
+                        kind: ViolationKind::IllegalNesting {
+                            parent: parent.map(str::to_owned),
+                            child: "text".to_owned(),
+                        },
+                    });
+                }
+            }
+            Node::NodeElement(element) => check_element(parent, element, path, violations),
+        }
+        path.pop();
+    }
+}
+
+fn check_element(
+    parent: Option<&str>,
+    element: &NodeElement,
+    path: &mut Vec<usize>,
+    violations: &mut Vec<Violation>,
+) {
+    let tag = element.tag.as_str();
+    let mut report = |kind| {
+        violations.push(Violation {
+            path: path.clone(),
+            kind,
+        })
+    };
+
+    if !ALLOWED_TAGS.contains(&tag) {
+        report(ViolationKind::DisallowedTag(tag.to_owned()));
+    } else if !can_contain(parent, tag) {
+        report(ViolationKind::IllegalNesting {
+            parent: parent.map(str::to_owned),
+            child: format!("<{}>", tag),
+        });
+    }
+
+    let required = match tag {
+        "a" => Some("href"),
+        "iframe" | "img" | "video" => Some("src"),
+        _ => None,
+    };
+    let attrs = element.attrs.as_ref();
+    for attribute in attrs.into_iter().flat_map(|attrs| attrs.keys()) {
+        if Some(attribute.as_str()) != required {
+            report(ViolationKind::DisallowedAttribute {
+                tag: tag.to_owned(),
+                attribute: attribute.to_owned(),
+            });
+        }
+    }
+    if let Some(required) = required {
+        let value = attrs
+            .and_then(|attrs| attrs.get(required))
+            .and_then(Option::as_deref);
+        if value.map_or("", str::trim).is_empty() {
+            report(ViolationKind::EmptyAttribute {
+                tag: tag.to_owned(),
+                attribute: required.to_owned(),
+            });
+        }
+    }
+
+    let children = element.children.as_deref().unwrap_or_default();
+    if VOID_TAGS.contains(&tag) && !children.is_empty() {
+        report(ViolationKind::IllegalNesting {
+            parent: Some(tag.to_owned()),
+            child: "children".to_owned(),
+        });
+    }
+    check_children(Some(tag), children, path, violations);
+}
+
+/// Whether an allowed `child` tag can be placed inside `parent`.
+pub(crate) fn can_contain(parent: Option<&str>, child: &str) -> bool {
+    match (parent, child) {
+        (Some("ul"), "li") | (Some("ol"), "li") => true,
+        (Some("ul"), _) | (Some("ol"), _) | (_, "li") => false,
+        (Some("figure"), "figcaption") => true,
+        (_, "figcaption") => false,
+        (Some("a"), "a") => false,
+        (Some(parent), child) if INLINE_CONTAINERS.contains(&parent) => {
+            !BLOCK_TAGS.contains(&child)
+        }
+        _ => true,
+    }
+}
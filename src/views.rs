@@ -0,0 +1,228 @@
+//! Typed getViews queries and view time series.
+use crate::{Error, Result};
+use chrono::{Datelike, Months, NaiveDate};
+use std::fmt;
+
+/// Time period to count the views of a page in.
+///
+/// ```
+/// use telegraph_rs::ViewsQuery;
+///
+/// let total = ViewsQuery::Total;
+/// let december = ViewsQuery::month(2016, 12);
+/// let noon = ViewsQuery::hour(2019, 5, 19, 12);
+/// assert!(ViewsQuery::month(2016, 13).validate().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ViewsQuery {
+    /// All views since the page was created.
+    #[default]
+    Total,
+    Year {
+        year: i32,
+    },
+    Month {
+        year: i32,
+        month: u32,
+    },
+    Day {
+        year: i32,
+        month: u32,
+        day: u32,
+    },
+    Hour {
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+    },
+}
+
+impl ViewsQuery {
+    pub fn year(year: i32) -> Self {
+        ViewsQuery::Year { year }
+    }
+
+    pub fn month(year: i32, month: u32) -> Self {
+        ViewsQuery::Month { year, month }
+    }
+
+    pub fn day(year: i32, month: u32, day: u32) -> Self {
+        ViewsQuery::Day { year, month, day }
+    }
+
+    pub fn hour(year: i32, month: u32, day: u32, hour: u32) -> Self {
+        ViewsQuery::Hour {
+            year,
+            month,
+            day,
+            hour,
+        }
+    }
+
+    /// Views on `date`.
+    pub fn date(date: NaiveDate) -> Self {
+        Self::day(date.year(), date.month(), date.day())
+    }
+
+    fn parts(&self) -> (Option<i32>, Option<u32>, Option<u32>, Option<u32>) {
+        match *self {
+            ViewsQuery::Total => (None, None, None, None),
+            ViewsQuery::Year { year } => (Some(year), None, None, None),
+            ViewsQuery::Month { year, month } => (Some(year), Some(month), None, None),
+            ViewsQuery::Day { year, month, day } => (Some(year), Some(month), Some(day), None),
+            ViewsQuery::Hour {
+                year,
+                month,
+                day,
+                hour,
+            } => (Some(year), Some(month), Some(day), Some(hour)),
+        }
+    }
+
+    /// Check the ranges accepted by the API: year 2000-2100, month 1-12,
+    /// a day existing in that month and hour 0-24.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(Error::InvalidViewsQuery(reason));
+        let (year, month, day, hour) = self.parts();
+        if let Some(year) = year {
+            if !(2000..=2100).contains(&year) {
+                return invalid(format!("year {} is not in 2000-2100", year));
+            }
+        }
+        if let Some(month) = month {
+            if !(1..=12).contains(&month) {
+                return invalid(format!("month {} is not in 1-12", month));
+            }
+        }
+        if let (Some(year), Some(month), Some(day)) = (year, month, day) {
+            if NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                return invalid(format!(
+                    "{}-{:02}-{:02} is not a valid date",
+                    year, month, day
+                ));
+            }
+        }
+        if let Some(hour) = hour {
+            if hour > 24 {
+                return invalid(format!("hour {} is not in 0-24", hour));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let (year, month, day, hour) = self.parts();
+        let mut params = vec![];
+        if let Some(year) = year {
+            params.push(("year", year.to_string()));
+        }
+        for (name, value) in [("month", month), ("day", day), ("hour", hour)] {
+            if let Some(value) = value {
+                params.push((name, value.to_string()));
+            }
+        }
+        params
+    }
+}
+
+/// Formats the period as `total`, `2019`, `2019-05`, `2019-05-19` or `2019-05-19T12`.
+impl fmt::Display for ViewsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ViewsQuery::Total => write!(f, "total"),
+            ViewsQuery::Year { year } => write!(f, "{}", year),
+            ViewsQuery::Month { year, month } => write!(f, "{}-{:02}", year, month),
+            ViewsQuery::Day { year, month, day } => {
+                write!(f, "{}-{:02}-{:02}", year, month, day)
+            }
+            ViewsQuery::Hour {
+                year,
+                month,
+                day,
+                hour,
+            } => write!(f, "{}-{:02}-{:02}T{:02}", year, month, day, hour),
+        }
+    }
+}
+
+/// Size of the periods of a [`ViewsSeries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    Hour,
+    Day,
+    Month,
+}
+
+impl Granularity {
+    /// Every period of this size between `from` and `to`, both included.
+    pub(crate) fn periods(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<ViewsQuery>> {
+        if from > to {
+            return Err(Error::InvalidViewsQuery(format!(
+                "{} is after {}",
+                from, to
+            )));
+        }
+        let mut periods = vec![];
+        match self {
+            Granularity::Hour => {
+                for date in from.iter_days().take_while(|date| *date <= to) {
+                    for hour in 0..24 {
+                        periods.push(ViewsQuery::hour(
+                            date.year(),
+                            date.month(),
+                            date.day(),
+                            hour,
+                        ));
+                    }
+                }
+            }
+            Granularity::Day => {
+                periods.extend(
+                    from.iter_days()
+                        .take_while(|date| *date <= to)
+                        .map(ViewsQuery::date),
+                );
+            }
+            Granularity::Month => {
+                let mut month = from.with_day(1).unwrap();
+                while month <= to {
+                    periods.push(ViewsQuery::month(month.year(), month.month()));
+                    month = month + Months::new(1);
+                }
+            }
+        }
+        Ok(periods)
+    }
+}
+
+/// Views of a page in one period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewsPoint {
+    pub period: ViewsQuery,
+    pub views: i32,
+}
+
+/// Views of a page over consecutive periods, created by [`Telegraph::views_series`](crate::Telegraph::views_series).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewsSeries {
+    pub path: String,
+    pub granularity: Granularity,
+    pub points: Vec<ViewsPoint>,
+}
+
+impl ViewsSeries {
+    /// Sum of the views of every period.
+    pub fn total(&self) -> i64 {
+        self.points.iter().map(|point| point.views as i64).sum()
+    }
+
+    /// Export as CSV with a `period,views` header, one period per line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("period,views\n");
+        for point in &self.points {
+            csv.push_str(&format!("{},{}\n", point.period, point.views));
+        }
+        csv
+    }
+}